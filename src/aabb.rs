@@ -0,0 +1,197 @@
+use super::ray::*;
+use super::vec3::*;
+
+// Axis aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub minimum: Point3,
+    pub maximum: Point3,
+}
+
+impl Aabb {
+    // The box spanned by two opposite corners, in any order.
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        Aabb {
+            minimum: Point3::new(f64::min(a.x, b.x), f64::min(a.y, b.y), f64::min(a.z, b.z)),
+            maximum: Point3::new(f64::max(a.x, b.x), f64::max(a.y, b.y), f64::max(a.z, b.z)),
+        }
+    }
+
+    // Contains nothing, the union with any box is that box.
+    pub fn empty() -> Aabb {
+        Aabb {
+            minimum: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            maximum: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Point3>>(points: I) -> Aabb {
+        points
+            .into_iter()
+            .fold(Aabb::empty(), |aabb, p| aabb.include(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.minimum.x > self.maximum.x
+            || self.minimum.y > self.maximum.y
+            || self.minimum.z > self.maximum.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            minimum: Point3::new(
+                f64::min(self.minimum.x, other.minimum.x),
+                f64::min(self.minimum.y, other.minimum.y),
+                f64::min(self.minimum.z, other.minimum.z),
+            ),
+            maximum: Point3::new(
+                f64::max(self.maximum.x, other.maximum.x),
+                f64::max(self.maximum.y, other.maximum.y),
+                f64::max(self.maximum.z, other.maximum.z),
+            ),
+        }
+    }
+
+    // Empty if the boxes don't overlap.
+    pub fn intersection(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            minimum: Point3::new(
+                f64::max(self.minimum.x, other.minimum.x),
+                f64::max(self.minimum.y, other.minimum.y),
+                f64::max(self.minimum.z, other.minimum.z),
+            ),
+            maximum: Point3::new(
+                f64::min(self.maximum.x, other.maximum.x),
+                f64::min(self.maximum.y, other.maximum.y),
+                f64::min(self.maximum.z, other.maximum.z),
+            ),
+        }
+    }
+
+    pub fn include(&self, p: Point3) -> Aabb {
+        self.union(&Aabb {
+            minimum: p,
+            maximum: p,
+        })
+    }
+
+    // Grows the box by margin on every side.
+    pub fn pad(&self, margin: f64) -> Aabb {
+        let margin = Vec3::new(margin, margin, margin);
+        Aabb {
+            minimum: self.minimum - margin,
+            maximum: self.maximum + margin,
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        (self.minimum + self.maximum) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.maximum - self.minimum
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (a, b) = (self.minimum, self.maximum);
+        [
+            Point3::new(a.x, a.y, a.z),
+            Point3::new(b.x, a.y, a.z),
+            Point3::new(a.x, b.y, a.z),
+            Point3::new(b.x, b.y, a.z),
+            Point3::new(a.x, a.y, b.z),
+            Point3::new(b.x, a.y, b.z),
+            Point3::new(a.x, b.y, b.z),
+            Point3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    pub fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> bool {
+        let inverse_direction = Vec3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        self.intersect(ray.origin, inverse_direction, minimum, maximum)
+            .is_some()
+    }
+
+    // Slab test, returns where the ray enters the box within the interval.
+    #[inline]
+    pub fn intersect(
+        &self,
+        origin: Point3,
+        inverse_direction: Vec3,
+        minimum: f64,
+        maximum: f64,
+    ) -> Option<f64> {
+        Some(self.clip(origin, inverse_direction, minimum, maximum)?.0)
+    }
+
+    // Where the ray enters and leaves the box within the interval.
+    #[inline]
+    pub fn clip(
+        &self,
+        origin: Point3,
+        inverse_direction: Vec3,
+        minimum: f64,
+        maximum: f64,
+    ) -> Option<(f64, f64)> {
+        let mut entry = minimum;
+        let mut exit = maximum;
+
+        for axis in 0..3 {
+            // Parallel to the slab, 0 * inf would be NaN at its faces.
+            if inverse_direction[axis].is_infinite() {
+                if origin[axis] < self.minimum[axis] || origin[axis] > self.maximum[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t0 = (self.minimum[axis] - origin[axis]) * inverse_direction[axis];
+            let t1 = (self.maximum[axis] - origin[axis]) * inverse_direction[axis];
+            let (near, far) = match t0 <= t1 {
+                true => (t0, t1),
+                false => (t1, t0),
+            };
+
+            entry = f64::max(entry, near);
+            exit = f64::min(exit, far);
+            if exit < entry {
+                return None;
+            }
+        }
+
+        Some((entry, exit))
+    }
+}
+
+#[test]
+fn hit_aabb() {
+    let aabb = Aabb::new(Point3::new(1.0, 1.0, 1.0), Point3::new(-1.0, -1.0, -1.0));
+    assert_eq!(aabb.minimum, Point3::new(-1.0, -1.0, -1.0));
+    assert_eq!(aabb.surface_area(), 24.0);
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(aabb.hit(&ray, 0.0, f64::INFINITY));
+    assert!(!aabb.hit(&ray, 0.0, 3.0));
+
+    let ray = Ray::new(Point3::new(0.0, 2.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(!aabb.hit(&ray, 0.0, f64::INFINITY));
+
+    // Parallel to and lying in a face.
+    let ray = Ray::new(Point3::new(1.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(aabb.hit(&ray, 0.0, f64::INFINITY));
+
+    assert!(Aabb::empty().is_empty());
+    assert_eq!(Aabb::empty().union(&aabb), aabb);
+}
@@ -0,0 +1,386 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::aabb::*;
+use super::hittable::*;
+use super::ray::*;
+use super::vec3::*;
+
+// Bounding volume hierarchy over a list of primitives given by their
+// bounding boxes, built with the surface area heuristic. The BVH only knows
+// primitive indices, the owner intersects the primitives themselves.
+
+#[derive(Clone, Debug, Default)]
+pub struct BvhStats {
+    pub build_time: Duration,
+    pub primitive_count: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub depth: usize,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} primitives, {} nodes, {} leaves, depth {}, built in {:.3}ms",
+            self.primitive_count,
+            self.node_count,
+            self.leaf_count,
+            self.depth,
+            self.build_time.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    // Leaves have count > 0 primitives starting at start in the index list,
+    // the children of inner nodes are the next node and right.
+    start: usize,
+    count: usize,
+    right: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+    stats: BvhStats,
+}
+
+const BIN_COUNT: usize = 16;
+const MAX_LEAF_SIZE: usize = 8;
+const MAX_DEPTH: usize = 64;
+const TRAVERSAL_COST: f64 = 1.0;
+
+struct BuildItem {
+    bounds: Aabb,
+    centroid: Point3,
+    index: usize,
+}
+
+impl Bvh {
+    pub fn build(boxes: &[Aabb]) -> Bvh {
+        let now = Instant::now();
+
+        let mut items: Vec<BuildItem> = boxes
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| BuildItem {
+                bounds: *bounds,
+                centroid: bounds.centroid(),
+                index,
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * boxes.len()),
+            indices: Vec::with_capacity(boxes.len()),
+            stats: BvhStats::default(),
+        };
+
+        if !items.is_empty() {
+            bvh.build_node(&mut items, 1);
+        }
+
+        bvh.stats.primitive_count = boxes.len();
+        bvh.stats.node_count = bvh.nodes.len();
+        bvh.stats.build_time = now.elapsed();
+        bvh
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bounds)
+    }
+
+    fn build_node(&mut self, items: &mut [BuildItem], depth: usize) -> usize {
+        let index = self.nodes.len();
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |b, item| b.union(&item.bounds));
+        self.nodes.push(BvhNode {
+            bounds,
+            start: 0,
+            count: 0,
+            right: 0,
+        });
+        self.stats.depth = usize::max(self.stats.depth, depth);
+
+        let split = match items.len() > 1 && depth < MAX_DEPTH {
+            true => self.find_split(items, &bounds),
+            false => None,
+        };
+
+        match split {
+            None => {
+                self.nodes[index].start = self.indices.len();
+                self.nodes[index].count = items.len();
+                self.indices.extend(items.iter().map(|item| item.index));
+                self.stats.leaf_count += 1;
+            }
+            Some(middle) => {
+                let (left, right) = items.split_at_mut(middle);
+                self.build_node(left, depth + 1);
+                let right = self.build_node(right, depth + 1);
+                self.nodes[index].right = right;
+            }
+        }
+
+        index
+    }
+
+    // Binned SAH, partitions items and returns the split position or None if
+    // a leaf is cheaper.
+    fn find_split(&self, items: &mut [BuildItem], bounds: &Aabb) -> Option<usize> {
+        let centroid_bounds = Aabb::from_points(items.iter().map(|item| item.centroid));
+        let extent = centroid_bounds.extent();
+
+        let mut best: Option<(usize, usize, f64)> = None;
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+
+            let bin_of = |item: &BuildItem| {
+                let offset = (item.centroid[axis] - centroid_bounds.minimum[axis]) / extent[axis];
+                usize::min((offset * BIN_COUNT as f64) as usize, BIN_COUNT - 1)
+            };
+
+            let mut bins = [(Aabb::empty(), 0usize); BIN_COUNT];
+            for item in items.iter() {
+                let bin = &mut bins[bin_of(item)];
+                bin.0 = bin.0.union(&item.bounds);
+                bin.1 += 1;
+            }
+
+            // Sweep from the right to get the area and count of every right
+            // side, then from the left to evaluate the splits.
+            let mut right_area = [0.0; BIN_COUNT];
+            let mut right_count = [0usize; BIN_COUNT];
+            let (mut accumulated, mut count) = (Aabb::empty(), 0);
+            for bin in (1..BIN_COUNT).rev() {
+                accumulated = accumulated.union(&bins[bin].0);
+                count += bins[bin].1;
+                right_area[bin] = accumulated.surface_area();
+                right_count[bin] = count;
+            }
+
+            let (mut accumulated, mut count) = (Aabb::empty(), 0);
+            for split in 1..BIN_COUNT {
+                accumulated = accumulated.union(&bins[split - 1].0);
+                count += bins[split - 1].1;
+                if count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+
+                let cost = accumulated.surface_area() * count as f64
+                    + right_area[split] * right_count[split] as f64;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        let parent_area = bounds.surface_area();
+        let leaf_cost = items.len() as f64;
+
+        let (axis, split) = match best {
+            Some((axis, split, cost)) => {
+                let split_cost = match parent_area > 0.0 {
+                    true => TRAVERSAL_COST + cost / parent_area,
+                    false => f64::INFINITY,
+                };
+                if split_cost >= leaf_cost && items.len() <= MAX_LEAF_SIZE {
+                    return None;
+                }
+                (axis, split)
+            }
+            // All centroids in one point, splitting can't separate them.
+            None if items.len() <= MAX_LEAF_SIZE => return None,
+            None => return Some(median_split(items)),
+        };
+
+        let mut middle = 0;
+        for i in 0..items.len() {
+            let offset = (items[i].centroid[axis] - centroid_bounds.minimum[axis]) / extent[axis];
+            let bin = usize::min((offset * BIN_COUNT as f64) as usize, BIN_COUNT - 1);
+            if bin < split {
+                items.swap(i, middle);
+                middle += 1;
+            }
+        }
+
+        match middle == 0 || middle == items.len() {
+            true => Some(median_split(items)),
+            false => Some(middle),
+        }
+    }
+
+    // Closest hit of all primitives, hit_primitive(index, minimum, maximum)
+    // intersects primitive index with the ray.
+    pub fn hit<F>(&self, ray: &Ray, minimum: f64, maximum: f64, mut hit_primitive: F) -> Option<Hit>
+    where
+        F: FnMut(usize, f64, f64) -> Option<Hit>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse_direction = Vec3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let entry = |node: usize, maximum: f64| {
+            self.nodes[node]
+                .bounds
+                .intersect(ray.origin, inverse_direction, minimum, maximum)
+        };
+
+        let mut closest = maximum;
+        let mut out: Option<Hit> = None;
+        let mut stack = [0usize; MAX_DEPTH + 1];
+        let mut top = 0;
+
+        entry(0, closest)?;
+        stack[top] = 0;
+        top += 1;
+
+        while top > 0 {
+            top -= 1;
+            let index = stack[top];
+            let node = &self.nodes[index];
+
+            if node.count > 0 {
+                for i in node.start..node.start + node.count {
+                    if let Some(hit) = hit_primitive(self.indices[i], minimum, closest) {
+                        closest = hit.solution;
+                        out = Some(hit);
+                    }
+                }
+                continue;
+            }
+
+            // Visit the nearer child first, it is pushed last.
+            let (left, right) = (index + 1, node.right);
+            match (entry(left, closest), entry(right, closest)) {
+                (Some(l), Some(r)) => {
+                    let (near, far) = match l <= r {
+                        true => (left, right),
+                        false => (right, left),
+                    };
+                    stack[top] = far;
+                    stack[top + 1] = near;
+                    top += 2;
+                }
+                (Some(_), None) => {
+                    stack[top] = left;
+                    top += 1;
+                }
+                (None, Some(_)) => {
+                    stack[top] = right;
+                    top += 1;
+                }
+                (None, None) => (),
+            }
+        }
+
+        out
+    }
+}
+
+fn median_split(items: &mut [BuildItem]) -> usize {
+    let bounds = Aabb::from_points(items.iter().map(|item| item.centroid));
+    let extent = bounds.extent();
+    let axis = match (
+        extent.x >= extent.y,
+        extent.x >= extent.z,
+        extent.y >= extent.z,
+    ) {
+        (true, true, _) => 0,
+        (false, _, true) => 1,
+        _ => 2,
+    };
+
+    let middle = items.len() / 2;
+    items.select_nth_unstable_by(middle, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    middle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::*;
+    use crate::sphere::*;
+    use rand::prelude::*;
+
+    #[test]
+    fn bvh_matches_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let spheres: Vec<Sphere> = (0..500)
+            .map(|_| {
+                Sphere::new(
+                    Point3::new(
+                        rng.gen_range(-10.0..10.0),
+                        rng.gen_range(-10.0..10.0),
+                        rng.gen_range(-10.0..10.0),
+                    ),
+                    rng.gen_range(0.05..0.5),
+                    Material::Dielectric(1.5),
+                )
+            })
+            .collect();
+
+        let boxes: Vec<Aabb> = spheres.iter().map(|s| s.bounding_box().unwrap()).collect();
+        let bvh = Bvh::build(&boxes);
+
+        let stats = bvh.stats();
+        assert_eq!(stats.primitive_count, 500);
+        assert_eq!(stats.node_count, 2 * stats.leaf_count - 1);
+        assert!(stats.depth < 40);
+
+        for _ in 0..2000 {
+            let ray = Ray::new(
+                Point3::new(
+                    rng.gen_range(-12.0..12.0),
+                    rng.gen_range(-12.0..12.0),
+                    rng.gen_range(-12.0..12.0),
+                ),
+                Vec3::random_in_unit_sphere(),
+            );
+
+            let expected = spheres
+                .iter()
+                .filter_map(|s| s.hit(&ray, 1e-6, f64::INFINITY))
+                .map(|h| h.solution)
+                .min_by(f64::total_cmp);
+            let found = bvh
+                .hit(&ray, 1e-6, f64::INFINITY, |i, minimum, maximum| {
+                    spheres[i].hit(&ray, minimum, maximum)
+                })
+                .map(|h| h.solution);
+
+            assert_eq!(expected, found);
+        }
+    }
+
+    #[test]
+    fn bvh_of_identical_boxes() {
+        let boxes = vec![Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)); 100];
+        let bvh = Bvh::build(&boxes);
+        assert_eq!(bvh.stats().primitive_count, 100);
+        assert!(bvh.stats().leaf_count > 1);
+
+        let empty = Bvh::build(&[]);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(empty
+            .hit(&ray, 0.0, f64::INFINITY, |_, _, _| None)
+            .is_none());
+    }
+}
@@ -1,12 +1,35 @@
+use rand::prelude::*;
+use std::any::Any;
+
 use super::ray::*;
 use super::vec3::*;
 
+// Projection from the image to rays. The renderer asks for rays through
+// points u, v on the image, both in -0.5..0.5 with v pointing up. Points the
+// projection doesn't cover have no ray and stay black. Any lets a scene hand
+// out the concrete camera.
+pub trait CameraModel: Any + Send + Sync {
+    fn cast_ray(&self, u: f64, v: f64) -> Option<Ray>;
+}
+
+// Perspective camera, the image is one unit high at focal_length in front of
+// the position.
 pub struct Camera {
     pub position: Point3,
     pub look_at: Point3,
     pub world_up: Vec3,
     pub focal_length: f64,
     pub aspec_ratio: f64,
+    // Rays are cast at random times while the shutter is open, objects
+    // moving in the meantime are blurred.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    // Thin lens, rays start on the aperture and meet again on the plane in
+    // focus. A zero aperture radius is a pinhole with everything in focus.
+    // Apertures with 3 or more blades are polygons instead of round.
+    pub aperture: f64,
+    pub focus_distance: f64,
+    pub aperture_blades: u32,
 
     screen_right: Vec3,
     screen_up: Vec3,
@@ -35,14 +58,438 @@ impl Camera {
             world_up,
             focal_length,
             aspec_ratio,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            aperture: 0.0,
+            focus_distance: (look_at - position).magnitude(),
+            aperture_blades: 0,
             screen_right,
             screen_up,
             screen_front,
         }
     }
 
-    pub fn cast_ray(&self, u: f64, v: f64) -> Ray {
+    // Field of view across the height of the image, in degrees.
+    pub fn with_vertical_fov(
+        position: Point3,
+        look_at: Point3,
+        world_up: Vec3,
+        vertical_fov: f64,
+        aspec_ratio: f64,
+    ) -> Camera {
+        let focal_length = 0.5 / f64::tan(vertical_fov.to_radians() * 0.5);
+        Camera::new(position, look_at, world_up, focal_length, aspec_ratio)
+    }
+
+    // Field of view across the width of the image, in degrees.
+    pub fn with_horizontal_fov(
+        position: Point3,
+        look_at: Point3,
+        world_up: Vec3,
+        horizontal_fov: f64,
+        aspec_ratio: f64,
+    ) -> Camera {
+        let focal_length = 0.5 * aspec_ratio / f64::tan(horizontal_fov.to_radians() * 0.5);
+        Camera::new(position, look_at, world_up, focal_length, aspec_ratio)
+    }
+
+    // Lens focal length and sensor width in millimeters. The sensor width
+    // covers the longer side of the image, like 36mm film does.
+    pub fn with_sensor(
+        position: Point3,
+        look_at: Point3,
+        world_up: Vec3,
+        focal_length: f64,
+        sensor_width: f64,
+        aspec_ratio: f64,
+    ) -> Camera {
+        let longer_side = f64::max(aspec_ratio, 1.0);
+        let focal_length = focal_length * longer_side / sensor_width;
+        Camera::new(position, look_at, world_up, focal_length, aspec_ratio)
+    }
+
+    // Field of view across the height of the image, in degrees.
+    pub fn vertical_fov(&self) -> f64 {
+        2.0 * f64::atan(0.5 / self.focal_length).to_degrees()
+    }
+}
+
+impl CameraModel for Camera {
+    fn cast_ray(&self, u: f64, v: f64) -> Option<Ray> {
         let direction = self.screen_front + (self.screen_right * u) + (self.screen_up * v);
-        Ray::new(self.position, direction - self.position)
+        let time = shutter_time(self.shutter_open, self.shutter_close);
+        let direction = direction - self.position;
+        if self.aperture <= 0.0 {
+            return Some(Ray::with_time(self.position, direction, time));
+        }
+
+        // The direction reaches the screen at focal_length along the view.
+        let focus = self.position + direction * (self.focus_distance / self.focal_length);
+        let (x, y) = sample_aperture(self.aperture_blades);
+        let right = self.screen_right / self.aspec_ratio;
+        let origin = self.position + (right * x + self.screen_up * y) * self.aperture;
+        Some(Ray::with_time(origin, focus - origin, time))
+    }
+}
+
+// Parallel rays along the view, the image is view_height high in world units.
+pub struct OrthographicCamera {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub world_up: Vec3,
+    pub view_height: f64,
+    pub aspec_ratio: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+
+    view: View,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        position: Point3,
+        look_at: Point3,
+        world_up: Vec3,
+        view_height: f64,
+        aspec_ratio: f64,
+    ) -> OrthographicCamera {
+        OrthographicCamera {
+            position,
+            look_at,
+            world_up,
+            view_height,
+            aspec_ratio,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            view: View::new(position, look_at, world_up),
+        }
+    }
+}
+
+impl CameraModel for OrthographicCamera {
+    fn cast_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let x = self.view.right * (u * self.view_height * self.aspec_ratio);
+        let y = self.view.up * (v * self.view_height);
+        Some(Ray::with_time(
+            self.position + x + y,
+            self.view.front,
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
     }
 }
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    // Distance from the center grows with the angle to the view.
+    Equidistant,
+    // Equal areas on the image cover equal solid angles.
+    Equisolid,
+}
+
+// Circular fisheye, the angle to the view grows with the distance from the
+// center of the image. The field of view in degrees spans the height of the
+// image, or the width with with_horizontal_fov, and may go up to 360.
+pub struct FisheyeCamera {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub world_up: Vec3,
+    pub fov: f64,
+    pub mapping: FisheyeMapping,
+    pub aspec_ratio: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+
+    // How wide fov is on the image, 1 is the image height.
+    span: f64,
+    view: View,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        position: Point3,
+        look_at: Point3,
+        world_up: Vec3,
+        fov: f64,
+        mapping: FisheyeMapping,
+        aspec_ratio: f64,
+    ) -> FisheyeCamera {
+        FisheyeCamera {
+            position,
+            look_at,
+            world_up,
+            fov,
+            mapping,
+            aspec_ratio,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            span: 1.0,
+            view: View::new(position, look_at, world_up),
+        }
+    }
+
+    pub fn with_horizontal_fov(
+        position: Point3,
+        look_at: Point3,
+        world_up: Vec3,
+        fov: f64,
+        mapping: FisheyeMapping,
+        aspec_ratio: f64,
+    ) -> FisheyeCamera {
+        let mut camera = FisheyeCamera::new(position, look_at, world_up, fov, mapping, aspec_ratio);
+        camera.span = aspec_ratio;
+        camera
+    }
+
+    // Whether fov spans the width rather than the height of the image.
+    pub fn is_horizontal(&self) -> bool {
+        self.span != 1.0
+    }
+}
+
+impl CameraModel for FisheyeCamera {
+    fn cast_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        // Distance from the center, 1 where the angle is half of fov.
+        let (x, y) = (u * self.aspec_ratio, v);
+        let radius = f64::sqrt(x * x + y * y) * 2.0 / self.span;
+        let half_fov = self.fov.to_radians() * 0.5;
+
+        let angle = match self.mapping {
+            FisheyeMapping::Equidistant => radius * half_fov,
+            FisheyeMapping::Equisolid => {
+                let s = radius * f64::sin(half_fov * 0.5);
+                if s > 1.0 {
+                    return None;
+                }
+                2.0 * f64::asin(s)
+            }
+        };
+        if angle > half_fov + 1e-12 {
+            return None;
+        }
+
+        let (sin, cos) = f64::sin_cos(angle);
+        let (x, y) = match radius > 0.0 {
+            true => (
+                x / (radius * self.span * 0.5),
+                y / (radius * self.span * 0.5),
+            ),
+            false => (0.0, 0.0),
+        };
+        let direction = self.view.front * cos + (self.view.right * x + self.view.up * y) * sin;
+        Some(Ray::with_time(
+            self.position,
+            direction,
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+// 360 by 180 degree panorama, u goes around from behind through look_at and
+// v from straight down to straight up. Images are usually twice as wide as
+// high.
+pub struct EquirectangularCamera {
+    pub position: Point3,
+    pub look_at: Point3,
+    pub world_up: Vec3,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+
+    view: View,
+}
+
+impl EquirectangularCamera {
+    pub fn new(position: Point3, look_at: Point3, world_up: Vec3) -> EquirectangularCamera {
+        EquirectangularCamera {
+            position,
+            look_at,
+            world_up,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            view: View::new(position, look_at, world_up),
+        }
+    }
+}
+
+impl CameraModel for EquirectangularCamera {
+    fn cast_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let (sin_longitude, cos_longitude) = f64::sin_cos(2.0 * PI * u);
+        let (sin_latitude, cos_latitude) = f64::sin_cos(PI * v);
+        let direction = self.view.front * (cos_latitude * cos_longitude)
+            + self.view.right * (cos_latitude * sin_longitude)
+            + self.view.up * sin_latitude;
+        Some(Ray::with_time(
+            self.position,
+            direction,
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+// Unit vectors of the view, world_up is only a hint for up.
+struct View {
+    right: Vec3,
+    up: Vec3,
+    front: Vec3,
+}
+
+impl View {
+    fn new(position: Point3, look_at: Point3, world_up: Vec3) -> View {
+        let front = (look_at - position).unit();
+        let right = front.cross(world_up).unit();
+        let up = right.cross(front).unit();
+        View { right, up, front }
+    }
+}
+
+fn shutter_time(open: f64, close: f64) -> f64 {
+    match close > open {
+        true => thread_rng().gen_range(open..close),
+        false => open,
+    }
+}
+
+// Uniform point on the unit disk, or on the regular polygon with a corner at
+// the top inscribed in it.
+fn sample_aperture(blades: u32) -> (f64, f64) {
+    let mut rng = thread_rng();
+
+    if blades < 3 {
+        let radius = f64::sqrt(rng.gen::<f64>());
+        let (sin, cos) = f64::sin_cos(2.0 * PI * rng.gen::<f64>());
+        return (radius * cos, radius * sin);
+    }
+
+    // One of the equal triangles between the center and two corners.
+    let wedge = 2.0 * PI / blades as f64;
+    let corner = |i: u32| {
+        let (sin, cos) = f64::sin_cos(PI / 2.0 + wedge * i as f64);
+        (cos, sin)
+    };
+    let i = rng.gen_range(0..blades);
+    let (a, b) = (corner(i), corner(i + 1));
+
+    let (mut s, mut t) = (rng.gen::<f64>(), rng.gen::<f64>());
+    if s + t > 1.0 {
+        (s, t) = (1.0 - s, 1.0 - t);
+    }
+    (a.0 * s + b.0 * t, a.1 * s + b.1 * t)
+}
+
+#[test]
+fn field_of_view() {
+    let position = Point3::new(0.0, 0.0, 0.0);
+    let look_at = Point3::new(0.0, 0.0, -1.0);
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+
+    let camera = Camera::with_vertical_fov(position, look_at, up, 90.0, 2.0);
+    assert!(close(camera.focal_length, 0.5));
+    assert!(close(camera.vertical_fov(), 90.0));
+    // The top edge of the image is 45 degrees up.
+    let ray = camera.cast_ray(0.0, 0.5).unwrap();
+    assert!(close(ray.direction.y, -ray.direction.z));
+
+    let camera = Camera::with_horizontal_fov(position, look_at, up, 90.0, 2.0);
+    assert!(close(camera.focal_length, 1.0));
+    let ray = camera.cast_ray(0.5, 0.0).unwrap();
+    assert!(close(ray.direction.x, -ray.direction.z));
+
+    // 50mm on 36mm film, about 39.6 degrees across the longer side.
+    let wide = Camera::with_sensor(position, look_at, up, 50.0, 36.0, 1.5);
+    let tall = Camera::with_sensor(position, look_at, up, 50.0, 36.0, 1.0 / 1.5);
+    let horizontal = 2.0 * f64::atan(18.0 / 50.0).to_degrees();
+    assert!(close(
+        Camera::with_horizontal_fov(position, look_at, up, horizontal, 1.5).focal_length,
+        wide.focal_length
+    ));
+    assert!(close(tall.vertical_fov(), horizontal));
+}
+
+#[test]
+fn thin_lens() {
+    let mut camera = Camera::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(0.0, 0.0, -10.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        1.0,
+        2.0,
+    );
+    assert_eq!(camera.focus_distance, 10.0);
+    camera.aperture = 0.5;
+    camera.focus_distance = 4.0;
+
+    // Rays through the same point on the screen meet on the focus plane.
+    let expected = Point3::new(0.25 * 2.0 * 4.0, 0.25 * 4.0, -4.0);
+    for blades in [0, 6] {
+        camera.aperture_blades = blades;
+        for _ in 0..100 {
+            let ray = camera.cast_ray(0.25, 0.25).unwrap();
+            assert!(ray.origin.magnitude() <= 0.5 + 1e-12);
+            assert_eq!(ray.origin.z, 0.0);
+            assert!(ray.at(1.0).nearly_eq(expected));
+        }
+    }
+
+    // A hexagon with a corner at the top reaches 1 up but only sqrt(3)/2 to
+    // the sides.
+    for _ in 0..1000 {
+        let (x, y) = sample_aperture(6);
+        assert!(x.abs() <= f64::sqrt(3.0) / 2.0 + 1e-12);
+        assert!(y.abs() <= 1.0 + 1e-12);
+        assert!(x.abs() * 0.5 + y.abs() * f64::sqrt(3.0) / 2.0 <= f64::sqrt(3.0) / 2.0 + 1e-12);
+    }
+}
+
+#[test]
+fn other_projections() {
+    let position = Point3::new(0.0, 0.0, 0.0);
+    let look_at = Point3::new(0.0, 0.0, -1.0);
+    let up = Vec3::new(0.0, 1.0, 0.0);
+
+    let camera = OrthographicCamera::new(position, look_at, up, 4.0, 2.0);
+    let ray = camera.cast_ray(0.5, -0.5).unwrap();
+    assert_eq!(ray.origin, Point3::new(4.0, -2.0, 0.0));
+    assert_eq!(ray.direction, Vec3::new(0.0, 0.0, -1.0));
+
+    // 180 degrees across the height, the top edge looks straight up.
+    for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+        let camera = FisheyeCamera::new(position, look_at, up, 180.0, mapping, 2.0);
+        let ray = camera.cast_ray(0.0, 0.5).unwrap();
+        assert!(ray.direction.nearly_eq(Vec3::new(0.0, 1.0, 0.0)));
+        let ray = camera.cast_ray(0.0, 0.0).unwrap();
+        assert!(ray.direction.nearly_eq(Vec3::new(0.0, 0.0, -1.0)));
+        assert!(camera.cast_ray(0.5, 0.0).is_none());
+    }
+    let camera = FisheyeCamera::new(
+        position,
+        look_at,
+        up,
+        180.0,
+        FisheyeMapping::Equidistant,
+        1.0,
+    );
+    let ray = camera.cast_ray(0.25, 0.0).unwrap();
+    let expected = Vec3::new(f64::sqrt(0.5), 0.0, -f64::sqrt(0.5));
+    assert!(ray.direction.nearly_eq(expected));
+    let camera = FisheyeCamera::with_horizontal_fov(
+        position,
+        look_at,
+        up,
+        180.0,
+        FisheyeMapping::Equidistant,
+        2.0,
+    );
+    assert!(camera
+        .cast_ray(0.5, 0.0)
+        .unwrap()
+        .direction
+        .nearly_eq(Vec3::new(1.0, 0.0, 0.0)));
+    assert!(camera.cast_ray(0.0, 0.5).is_some());
+
+    let camera = EquirectangularCamera::new(position, look_at, up);
+    let direction = |u, v| camera.cast_ray(u, v).unwrap().direction;
+    assert!(direction(0.0, 0.0).nearly_eq(Vec3::new(0.0, 0.0, -1.0)));
+    assert!(direction(0.25, 0.0).nearly_eq(Vec3::new(1.0, 0.0, 0.0)));
+    assert!(direction(0.5, 0.0).nearly_eq(Vec3::new(0.0, 0.0, 1.0)));
+    assert!(direction(0.1, 0.5).nearly_eq(Vec3::new(0.0, 1.0, 0.0)));
+}
@@ -0,0 +1,159 @@
+use super::aabb::*;
+use super::cylinder::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::vec3::*;
+
+// Cone with its base disk centered on base and its apex height above it
+// along +y, the base may be closed by a cap. Other orientations are a Cone
+// inside a Transformed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cone {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Material,
+}
+
+impl Cone {
+    pub fn new(base: Point3, radius: f64, height: f64, capped: bool, material: Material) -> Cone {
+        Cone {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+
+    pub fn base(&self) -> Point3 {
+        self.base
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    pub fn capped(&self) -> bool {
+        self.capped
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let o = ray.origin - self.base;
+        let d = ray.direction;
+        let mut closest: Option<(f64, Vec3, (f64, f64))> = None;
+        let mut maximum = maximum;
+
+        // x^2 + z^2 = (slope (height - y))^2, w is the distance below the
+        // apex.
+        let slope = self.radius / self.height;
+        let s2 = slope * slope;
+        let w = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - s2 * d.y * d.y;
+        let half_b = o.x * d.x + o.z * d.z + s2 * w * d.y;
+        let c = o.x * o.x + o.z * o.z - s2 * w * w;
+
+        if let Some(roots) = solve_quadratic(a, half_b, c) {
+            for t in roots {
+                let y = o.y + t * d.y;
+                if t >= minimum && t <= maximum && (0.0..=self.height).contains(&y) {
+                    let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+                    let normal = Vec3::new(x, s2 * (self.height - y), z);
+                    let normal = match normal.nearly_zero() {
+                        true => Vec3::new(0.0, 1.0, 0.0),
+                        false => normal.unit(),
+                    };
+                    closest = Some((t, normal, (side_u(x, z), y / self.height)));
+                    maximum = t;
+                    break;
+                }
+            }
+        }
+
+        if self.capped && d.y != 0.0 {
+            let t = -o.y / d.y;
+            let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+            if t >= minimum && t <= maximum && x * x + z * z <= self.radius * self.radius {
+                let uv = cap_uv(x, z, self.radius);
+                closest = Some((t, Vec3::new(0.0, -1.0, 0.0), uv));
+            }
+        }
+
+        let (solution, normal, uv) = closest?;
+        let front = ray.direction.dot(normal) < 0.0;
+        let mut hit = Hit::new(ray.at(solution), normal, solution, front, self.material);
+        hit.uv = uv;
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::new(
+            self.base - Vec3::new(r, 0.0, r),
+            self.base + Vec3::new(r, self.height, r),
+        ))
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        match self.capped {
+            true => Some(convex_spans(self, ray)),
+            false => None,
+        }
+    }
+}
+
+#[test]
+fn hit_cone() {
+    let cone = Cone::new(
+        Point3::new(0.0, 0.0, 0.0),
+        1.0,
+        1.0,
+        true,
+        Material::Dielectric(1.5),
+    );
+
+    // Halfway up the radius is 0.5, the side leans at 45 degrees.
+    let ray = Ray::new(Point3::new(5.0, 0.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    let hit = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert!((hit.solution - 4.5).abs() < 1e-9);
+    let expected = Vec3::new(1.0, 1.0, 0.0).unit();
+    assert!(hit.normal.nearly_eq(expected));
+
+    // The other nappe of the double cone above the apex is not part of it.
+    let ray = Ray::new(Point3::new(5.0, 1.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    assert!(cone.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+    let ray = Ray::new(Point3::new(0.25, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    let hit = cone.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hit.solution, 2.0);
+    assert_eq!(hit.normal, Vec3::new(0.0, -1.0, 0.0));
+
+    let open = Cone::new(
+        Point3::new(0.0, 0.0, 0.0),
+        1.0,
+        1.0,
+        false,
+        Material::Dielectric(1.5),
+    );
+    let hit = open.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert!((hit.point.y - 0.75).abs() < 1e-9);
+}
@@ -0,0 +1,245 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::ray::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // The left object with the right one carved out.
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, left: bool, right: bool) -> bool {
+        match self {
+            CsgOperation::Union => left || right,
+            CsgOperation::Intersection => left && right,
+            CsgOperation::Difference => left && !right,
+        }
+    }
+}
+
+// Constructive solid geometry over two closed objects, the spans of both
+// along a ray are combined by the operation. Objects that are not closed
+// count as empty. Csg objects are closed and can be combined again.
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<dyn Hittable + Send + Sync>,
+    right: Box<dyn Hittable + Send + Sync>,
+}
+
+impl Csg {
+    pub fn new<L, R>(operation: CsgOperation, left: L, right: R) -> Csg
+    where
+        L: Hittable + Send + Sync,
+        R: Hittable + Send + Sync,
+    {
+        Csg::from_boxed(operation, Box::new(left), Box::new(right))
+    }
+
+    pub fn from_boxed(
+        operation: CsgOperation,
+        left: Box<dyn Hittable + Send + Sync>,
+        right: Box<dyn Hittable + Send + Sync>,
+    ) -> Csg {
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union<L, R>(left: L, right: R) -> Csg
+    where
+        L: Hittable + Send + Sync,
+        R: Hittable + Send + Sync,
+    {
+        Csg::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection<L, R>(left: L, right: R) -> Csg
+    where
+        L: Hittable + Send + Sync,
+        R: Hittable + Send + Sync,
+    {
+        Csg::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference<L, R>(left: L, right: R) -> Csg
+    where
+        L: Hittable + Send + Sync,
+        R: Hittable + Send + Sync,
+    {
+        Csg::new(CsgOperation::Difference, left, right)
+    }
+
+    pub fn operation(&self) -> CsgOperation {
+        self.operation
+    }
+
+    pub fn left(&self) -> &(dyn Hittable + Send + Sync) {
+        &*self.left
+    }
+
+    pub fn right(&self) -> &(dyn Hittable + Send + Sync) {
+        &*self.right
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let in_range = |hit: &Hit| hit.solution >= minimum && hit.solution <= maximum;
+
+        for span in self.spans(ray)? {
+            if span.enter.solution > maximum {
+                break;
+            }
+            if in_range(&span.enter) {
+                return Some(span.enter);
+            }
+            if in_range(&span.exit) {
+                return Some(span.exit);
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let left = self.left.bounding_box();
+        let right = self.right.bounding_box();
+        match self.operation {
+            CsgOperation::Union => Some(left?.union(&right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(l), Some(r)) => Some(l.intersection(&r)),
+                (l, r) => l.or(r),
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        let left = self.left.spans(ray).unwrap_or_default();
+        let right = self.right.spans(ray).unwrap_or_default();
+        Some(combine(self.operation, left, right))
+    }
+}
+
+// Walks the boundaries of both objects along the ray and keeps those where
+// being inside the result changes.
+fn combine(operation: CsgOperation, left: Vec<Span>, right: Vec<Span>) -> Vec<Span> {
+    let mut boundaries: Vec<(Hit, bool, bool)> = Vec::new();
+    for (spans, is_left) in [(left, true), (right, false)] {
+        for span in spans {
+            boundaries.push((span.enter, is_left, true));
+            boundaries.push((span.exit, is_left, false));
+        }
+    }
+    boundaries.sort_by(|a, b| a.0.solution.total_cmp(&b.0.solution));
+
+    let (mut in_left, mut in_right, mut inside) = (false, false, false);
+    let mut enter: Option<Hit> = None;
+    let mut spans = Vec::new();
+
+    for (mut hit, is_left, entering) in boundaries {
+        match is_left {
+            true => in_left = entering,
+            false => in_right = entering,
+        }
+
+        let now = operation.inside(in_left, in_right);
+        if now && !inside {
+            hit.front = true;
+            enter = Some(hit);
+        } else if !now && inside {
+            hit.front = false;
+            if let Some(enter) = enter.take() {
+                spans.push(Span { enter, exit: hit });
+            }
+        }
+        inside = now;
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::*;
+    use crate::plane::*;
+    use crate::sphere::*;
+    use crate::vec3::*;
+
+    fn sphere(x: f64, material: Material) -> Sphere {
+        Sphere::new(Point3::new(x, 0.0, 0.0), 1.0, material)
+    }
+
+    #[test]
+    fn combine_spheres() {
+        let (a, b) = (Material::Dielectric(1.5), Material::Dielectric(1.3));
+        let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let solutions = |csg: &Csg| -> Vec<(f64, f64)> {
+            csg.spans(&ray)
+                .unwrap()
+                .iter()
+                .map(|s| (s.enter.solution, s.exit.solution))
+                .collect()
+        };
+
+        let union = Csg::union(sphere(0.0, a), sphere(1.0, b));
+        assert_eq!(solutions(&union), vec![(4.0, 7.0)]);
+        let union = Csg::union(sphere(0.0, a), sphere(3.0, b));
+        assert_eq!(solutions(&union), vec![(4.0, 6.0), (7.0, 9.0)]);
+
+        let lens = Csg::intersection(sphere(0.0, a), sphere(1.0, b));
+        assert_eq!(solutions(&lens), vec![(5.0, 6.0)]);
+        let hit = lens.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.front);
+        assert_eq!(hit.material, b);
+        assert_eq!(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+
+        // The carved surface belongs to the right object, seen from inside.
+        let bitten = Csg::difference(sphere(0.0, a), sphere(1.0, b));
+        assert_eq!(solutions(&bitten), vec![(4.0, 5.0)]);
+        let hit = bitten.hit(&ray, 4.5, f64::INFINITY).unwrap();
+        assert!(!hit.front);
+        assert_eq!(hit.solution, 5.0);
+        assert_eq!(hit.material, b);
+        assert_eq!(hit.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(bitten.hit(&ray, 5.5, f64::INFINITY).is_none());
+
+        // Nested, a hollow shell.
+        let shell = Csg::difference(
+            sphere(0.0, a),
+            Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5, b),
+        );
+        let cut = Csg::difference(shell, sphere(-1.0, b));
+        assert_eq!(solutions(&cut), vec![(5.5, 6.0)]);
+    }
+
+    #[test]
+    fn open_operands_are_empty() {
+        let material = Material::Dielectric(1.5);
+        let floor = Plane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material,
+        );
+        let csg = Csg::union(floor, sphere(3.0, material));
+        assert!(csg.bounding_box().is_none());
+
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(csg.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+        let intersection = Csg::intersection(sphere(0.0, material), sphere(1.0, material));
+        assert_eq!(
+            intersection.bounding_box(),
+            Some(Aabb::new(
+                Point3::new(0.0, -1.0, -1.0),
+                Point3::new(1.0, 1.0, 1.0)
+            ))
+        );
+    }
+}
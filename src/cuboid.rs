@@ -0,0 +1,114 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::quad::*;
+use super::ray::*;
+use super::vec3::*;
+
+// Closed axis aligned box made of six quads facing outwards. Rotated boxes
+// are a Cuboid inside a Transformed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cuboid {
+    minimum: Point3,
+    maximum: Point3,
+    sides: [Quad; 6],
+}
+
+impl Cuboid {
+    // Spanned by two opposite corners, in any order.
+    pub fn new(a: Point3, b: Point3, material: Material) -> Cuboid {
+        let Aabb { minimum, maximum } = Aabb::new(a, b);
+        let extent = maximum - minimum;
+        let dx = Vec3::new(extent.x, 0.0, 0.0);
+        let dy = Vec3::new(0.0, extent.y, 0.0);
+        let dz = Vec3::new(0.0, 0.0, extent.z);
+        let (min, max) = (minimum, maximum);
+
+        let sides = [
+            Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, material),
+            Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, material),
+            Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, material),
+            Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, material),
+            Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, material),
+            Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, material),
+        ];
+
+        Cuboid {
+            minimum,
+            maximum,
+            sides,
+        }
+    }
+
+    pub fn minimum(&self) -> Point3 {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> Point3 {
+        self.maximum
+    }
+
+    pub fn material(&self) -> Material {
+        self.sides[0].material()
+    }
+
+    pub fn sides(&self) -> &[Quad; 6] {
+        &self.sides
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let mut maximum_solution = maximum;
+        let mut out: Option<Hit> = None;
+
+        for side in self.sides.iter() {
+            if let Some(hit) = side.hit(ray, minimum, maximum_solution) {
+                maximum_solution = hit.solution;
+                out = Some(hit);
+            }
+        }
+
+        out
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            minimum: self.minimum,
+            maximum: self.maximum,
+        })
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        Some(convex_spans(self, ray))
+    }
+}
+
+#[test]
+fn hit_cuboid() {
+    let cuboid = Cuboid::new(
+        Point3::new(1.0, 1.0, 1.0),
+        Point3::new(-1.0, 0.0, -1.0),
+        Material::Dielectric(1.5),
+    );
+    assert_eq!(cuboid.minimum(), Point3::new(-1.0, 0.0, -1.0));
+
+    // Every side faces outwards.
+    for side in cuboid.sides() {
+        let center = side.corner() + (side.sides().0 + side.sides().1) * 0.5;
+        let inside = Point3::new(0.0, 0.5, 0.0);
+        assert!((center - inside).dot(side.normal()) > 0.0);
+    }
+
+    let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+    let hit = cuboid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert_eq!(hit.solution, 4.0);
+    assert_eq!(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+
+    // From inside the exit is a back face.
+    let ray = Ray::new(Point3::new(0.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    let hit = cuboid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert_eq!(hit.point, Point3::new(1.0, 0.5, 0.0));
+}
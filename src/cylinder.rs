@@ -0,0 +1,193 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::vec3::*;
+
+// Cylinder standing on base along +y, open or closed by caps. Other
+// orientations are a Cylinder inside a Transformed. The side uv coordinates
+// wrap around the axis and run up the height.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cylinder {
+    base: Point3,
+    radius: f64,
+    height: f64,
+    capped: bool,
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: Material,
+    ) -> Cylinder {
+        Cylinder {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+
+    pub fn base(&self) -> Point3 {
+        self.base
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    pub fn capped(&self) -> bool {
+        self.capped
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let o = ray.origin - self.base;
+        let d = ray.direction;
+        let r2 = self.radius * self.radius;
+        let mut closest: Option<(f64, Vec3, (f64, f64))> = None;
+        let mut maximum = maximum;
+
+        let a = d.x * d.x + d.z * d.z;
+        let half_b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - r2;
+        if let Some(roots) = solve_quadratic(a, half_b, c) {
+            for t in roots {
+                let y = o.y + t * d.y;
+                if t >= minimum && t <= maximum && (0.0..=self.height).contains(&y) {
+                    let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+                    let normal = Vec3::new(x, 0.0, z) / self.radius;
+                    closest = Some((t, normal, (side_u(x, z), y / self.height)));
+                    maximum = t;
+                    break;
+                }
+            }
+        }
+
+        if self.capped && d.y != 0.0 {
+            for (y, normal) in [(0.0, -1.0), (self.height, 1.0)] {
+                let t = (y - o.y) / d.y;
+                let (x, z) = (o.x + t * d.x, o.z + t * d.z);
+                if t >= minimum && t <= maximum && x * x + z * z <= r2 {
+                    let uv = cap_uv(x, z, self.radius);
+                    closest = Some((t, Vec3::new(0.0, normal, 0.0), uv));
+                    maximum = t;
+                }
+            }
+        }
+
+        let (solution, normal, uv) = closest?;
+        let front = ray.direction.dot(normal) < 0.0;
+        let mut hit = Hit::new(ray.at(solution), normal, solution, front, self.material);
+        hit.uv = uv;
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius;
+        Some(Aabb::new(
+            self.base - Vec3::new(r, 0.0, r),
+            self.base + Vec3::new(r, self.height, r),
+        ))
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        match self.capped {
+            true => Some(convex_spans(self, ray)),
+            false => None,
+        }
+    }
+}
+
+// Solutions of a t^2 + 2 half_b t + c = 0 in increasing order, a single
+// solution is returned twice when a is zero.
+pub fn solve_quadratic(a: f64, half_b: f64, c: f64) -> Option<[f64; 2]> {
+    if f64::abs(a) < 1e-12 {
+        if half_b == 0.0 {
+            return None;
+        }
+        let t = -c / (2.0 * half_b);
+        return Some([t, t]);
+    }
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let root = f64::sqrt(discriminant);
+    let (t0, t1) = ((-half_b - root) / a, (-half_b + root) / a);
+    match t0 <= t1 {
+        true => Some([t0, t1]),
+        false => Some([t1, t0]),
+    }
+}
+
+// Around the y axis, as for spheres.
+pub(crate) fn side_u(x: f64, z: f64) -> f64 {
+    0.5 + f64::atan2(-z, x) / (2.0 * PI)
+}
+
+// Caps fit the unit square.
+pub(crate) fn cap_uv(x: f64, z: f64, radius: f64) -> (f64, f64) {
+    (0.5 + 0.5 * x / radius, 0.5 + 0.5 * z / radius)
+}
+
+#[test]
+fn hit_cylinder() {
+    let material = Material::Dielectric(1.5);
+    let cylinder = Cylinder::new(Point3::new(0.0, 1.0, 0.0), 1.0, 2.0, true, material);
+    assert_eq!(
+        cylinder.bounding_box(),
+        Some(Aabb::new(
+            Point3::new(-1.0, 1.0, -1.0),
+            Point3::new(1.0, 3.0, 1.0)
+        ))
+    );
+
+    let ray = Ray::new(Point3::new(5.0, 2.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    let hit = cylinder.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert_eq!(hit.solution, 4.0);
+    assert_eq!(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+    assert_eq!(hit.uv.1, 0.5);
+
+    // Through the top cap, then the bottom cap from inside.
+    let ray = Ray::new(Point3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+    let hit = cylinder.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hit.solution, 2.0);
+    assert_eq!(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+    let hit = cylinder.hit(&ray, 2.5, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert_eq!(hit.solution, 4.0);
+
+    // Open tubes are hit on the inside of the far wall.
+    let open = Cylinder::new(Point3::new(0.0, 1.0, 0.0), 1.0, 2.0, false, material);
+    let ray = Ray::new(Point3::new(0.0, 3.5, 0.0), Vec3::new(1.0, -1.0, 0.0));
+    let hit = open.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert!((hit.point.x - 1.0).abs() < 1e-9);
+
+    // Above, beside.
+    let ray = Ray::new(Point3::new(5.0, 3.5, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+    assert!(cylinder.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
@@ -0,0 +1,1003 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::camera::*;
+use super::material::*;
+use super::mesh::*;
+use super::transform::*;
+use super::vec3::*;
+
+// glTF 2.0 import (.gltf with embedded or external buffers, and .glb).
+// Meshes are flattened into world space by the node hierarchy of the
+// default scene, perspective cameras are mapped onto Camera and the
+// metallic-roughness materials are approximated with Material.
+
+#[derive(Debug)]
+pub struct GltfError {
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+#[derive(Debug)]
+pub struct GltfMesh {
+    pub name: String,
+    pub mesh: Mesh,
+}
+
+#[derive(Clone, Debug)]
+pub struct GltfCamera {
+    pub name: String,
+    pub position: Point3,
+    pub look_at: Point3,
+    pub world_up: Vec3,
+    pub focal_length: f64,
+    pub aspect_ratio: Option<f64>,
+}
+
+impl GltfCamera {
+    // The aspect ratio of the file wins over the given one if it has one.
+    pub fn to_camera(&self, aspect_ratio: f64) -> Camera {
+        Camera::new(
+            self.position,
+            self.look_at,
+            self.world_up,
+            self.focal_length,
+            self.aspect_ratio.unwrap_or(aspect_ratio),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfScene, GltfError> {
+    let path = path.as_ref();
+    let filename = path.display().to_string();
+    let bytes = fs::read(path).map_err(|e| GltfError {
+        file: filename.clone(),
+        message: e.to_string(),
+    })?;
+
+    parse_gltf(&bytes, &filename, path.parent())
+}
+
+// External buffers are looked up in directory, without one only embedded
+// (data URI or .glb) buffers can be used.
+pub fn parse_gltf(
+    bytes: &[u8],
+    filename: &str,
+    directory: Option<&Path>,
+) -> Result<GltfScene, GltfError> {
+    let error = |message: String| GltfError {
+        file: filename.to_string(),
+        message,
+    };
+
+    let (json, binary) = match bytes.starts_with(b"glTF") {
+        true => split_glb(bytes).map_err(error)?,
+        false => (bytes, None),
+    };
+
+    let document: Document =
+        serde_json::from_slice(json).map_err(|e| error(format!("invalid glTF JSON: {}", e)))?;
+
+    let mut buffers = Vec::<Vec<u8>>::new();
+    for (index, buffer) in document.buffers.iter().enumerate() {
+        let data = match &buffer.uri {
+            None => match (index, binary) {
+                (0, Some(binary)) => binary.to_vec(),
+                _ => return Err(error(format!("buffer {} has no data", index))),
+            },
+            Some(uri) => load_uri(uri, directory).map_err(error)?,
+        };
+        if data.len() < buffer.byte_length {
+            return Err(error(format!(
+                "buffer {} has {} bytes, expected {}",
+                index,
+                data.len(),
+                buffer.byte_length
+            )));
+        }
+        buffers.push(data);
+    }
+
+    let loader = Loader {
+        document: &document,
+        buffers,
+        materials: document.materials.iter().map(to_material).collect(),
+    };
+    loader.load().map_err(error)
+}
+
+fn split_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    const JSON_CHUNK: u32 = 0x4E4F534A;
+    const BIN_CHUNK: u32 = 0x004E4942;
+
+    let read_u32 = |offset: usize| -> Result<u32, String> {
+        bytes
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| String::from("truncated .glb file"))
+    };
+
+    if read_u32(4)? != 2 {
+        return Err(String::from("only glTF version 2 is supported"));
+    }
+
+    let length = usize::min(read_u32(8)? as usize, bytes.len());
+    let mut offset = 12;
+    let mut json = None;
+    let mut binary = None;
+
+    while offset + 8 <= length {
+        let chunk_length = read_u32(offset)? as usize;
+        let chunk_type = read_u32(offset + 4)?;
+        let chunk = bytes
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| String::from("truncated .glb chunk"))?;
+
+        match chunk_type {
+            JSON_CHUNK if json.is_none() => json = Some(chunk),
+            BIN_CHUNK if binary.is_none() => binary = Some(chunk),
+            _ => (),
+        }
+
+        offset += 8 + ((chunk_length + 3) & !3);
+    }
+
+    match json {
+        Some(json) => Ok((json, binary)),
+        None => Err(String::from(".glb file has no JSON chunk")),
+    }
+}
+
+fn load_uri(uri: &str, directory: Option<&Path>) -> Result<Vec<u8>, String> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        return match data.find(";base64,") {
+            Some(index) => decode_base64(&data[index + 8..]),
+            None => Err(String::from("only base64 data URIs are supported")),
+        };
+    }
+
+    match directory {
+        Some(directory) => {
+            let path = directory.join(percent_decode(uri));
+            fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+        }
+        None => Err(format!("external buffer `{}` can not be loaded", uri)),
+    }
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = uri
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut count = 0;
+
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return Err(format!("invalid base64 character `{}`", c as char)),
+        };
+
+        bits = ((bits << 6) | value as u32) & 0xFFFF;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+// Approximation of the metallic-roughness model: emissive materials are
+// lights, transmissive ones dielectrics, mostly metallic ones metals with the
+// roughness as fuzziness, everything else is diffuse with the base color.
+fn to_material(material: &GltfMaterial) -> Material {
+    let emissive = material.emissive_factor;
+    let emissive = Color::new(emissive[0], emissive[1], emissive[2]);
+    if !emissive.nearly_zero() {
+        let strength = material
+            .extensions
+            .khr_materials_emissive_strength
+            .as_ref()
+            .map_or(1.0, |e| e.emissive_strength);
+        return Material::Emissive(emissive, strength);
+    }
+
+    let pbr = &material.pbr_metallic_roughness;
+    let base = pbr.base_color_factor;
+    let color = Color::new(base[0], base[1], base[2]);
+
+    let ior = material
+        .extensions
+        .khr_materials_ior
+        .as_ref()
+        .map_or(1.5, |e| e.ior);
+    let transmission = material
+        .extensions
+        .khr_materials_transmission
+        .as_ref()
+        .map_or(0.0, |e| e.transmission_factor);
+
+    if transmission >= 0.5 || (material.alpha_mode == "BLEND" && base[3] < 0.5) {
+        // Volumes absorb light inside, an infinite distance absorbs none.
+        let (attenuation, distance) = material
+            .extensions
+            .khr_materials_volume
+            .as_ref()
+            .filter(|v| v.attenuation_distance.is_finite())
+            .map_or(([1.0, 1.0, 1.0], 1.0), |v| {
+                (v.attenuation_color, v.attenuation_distance)
+            });
+        let roughness = pbr.roughness_factor;
+        if roughness <= 0.0 && attenuation == [1.0, 1.0, 1.0] {
+            return Material::Dielectric(ior);
+        }
+        Material::Glass {
+            refraction_index: ior,
+            roughness,
+            color: Color::new(attenuation[0], attenuation[1], attenuation[2]),
+            distance,
+        }
+    } else if pbr.metallic_factor >= 0.5 {
+        Material::Metalic(color, pbr.roughness_factor)
+    } else {
+        Material::Diffuse(color)
+    }
+}
+
+struct Loader<'a> {
+    document: &'a Document,
+    buffers: Vec<Vec<u8>>,
+    materials: Vec<Material>,
+}
+
+impl<'a> Loader<'a> {
+    fn load(&self) -> Result<GltfScene, String> {
+        let mut scene = GltfScene::default();
+
+        let roots: Vec<usize> = match (self.document.scene, self.document.scenes.is_empty()) {
+            (Some(index), _) => self
+                .document
+                .scenes
+                .get(index)
+                .ok_or_else(|| format!("scene {} does not exist", index))?
+                .nodes
+                .clone(),
+            (None, false) => self.document.scenes[0].nodes.clone(),
+            // Without scenes every node that isn't a child is a root.
+            (None, true) => {
+                let children: Vec<usize> = self
+                    .document
+                    .nodes
+                    .iter()
+                    .flat_map(|n| n.children.iter().copied())
+                    .collect();
+                (0..self.document.nodes.len())
+                    .filter(|n| !children.contains(n))
+                    .collect()
+            }
+        };
+
+        for root in roots {
+            self.load_node(root, &Transform::identity(), &mut scene, 0)?;
+        }
+
+        Ok(scene)
+    }
+
+    fn load_node(
+        &self,
+        index: usize,
+        parent: &Transform,
+        scene: &mut GltfScene,
+        depth: usize,
+    ) -> Result<(), String> {
+        // Guards against cycles in malformed files.
+        if depth > 256 {
+            return Err(String::from("node hierarchy is too deep"));
+        }
+
+        let node = self
+            .document
+            .nodes
+            .get(index)
+            .ok_or_else(|| format!("node {} does not exist", index))?;
+        // Zero scale hides a node and everything below it.
+        let transform = match node.local_transform() {
+            Some(local) => *parent * local,
+            None => return Ok(()),
+        };
+        let name = node
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("node {}", index));
+
+        if let Some(mesh) = node.mesh {
+            let mesh = self.load_mesh(mesh, &transform)?;
+            scene.meshes.push(GltfMesh {
+                name: name.clone(),
+                mesh,
+            });
+        }
+
+        if let Some(camera) = node.camera {
+            let camera = self
+                .document
+                .cameras
+                .get(camera)
+                .ok_or_else(|| format!("camera {} does not exist", camera))?;
+
+            // Orthographic cameras have no equivalent.
+            if let Some(perspective) = &camera.perspective {
+                let position = transform.point(Point3::new(0.0, 0.0, 0.0));
+                let front = transform.vector(Vec3::new(0.0, 0.0, -1.0)).unit();
+                let up = transform.vector(Vec3::new(0.0, 1.0, 0.0)).unit();
+
+                // The screen spans one unit vertically at focal_length.
+                scene.cameras.push(GltfCamera {
+                    name: name.clone(),
+                    position,
+                    look_at: position + front,
+                    world_up: up,
+                    focal_length: 0.5 / f64::tan(perspective.yfov * 0.5),
+                    aspect_ratio: perspective.aspect_ratio,
+                });
+            }
+        }
+
+        for child in node.children.iter() {
+            self.load_node(*child, &transform, scene, depth + 1)?;
+        }
+
+        Ok(())
+    }
+
+    fn load_mesh(&self, index: usize, transform: &Transform) -> Result<Mesh, String> {
+        let gltf_mesh = self
+            .document
+            .meshes
+            .get(index)
+            .ok_or_else(|| format!("mesh {} does not exist", index))?;
+
+        let mut mesh = Mesh::new();
+        let mut materials = HashMap::<Option<usize>, usize>::new();
+        let flips_winding = transform.determinant() < 0.0;
+
+        for primitive in gltf_mesh.primitives.iter() {
+            // Only triangle lists, strips and fans can be rendered.
+            if !matches!(primitive.mode, 4..=6) {
+                continue;
+            }
+
+            let position_accessor = *primitive
+                .attributes
+                .get("POSITION")
+                .ok_or_else(|| format!("mesh {} has a primitive without positions", index))?;
+            let positions = self.read_accessor(position_accessor, 3)?;
+            let normals = match primitive.attributes.get("NORMAL") {
+                Some(accessor) => Some(self.read_accessor(*accessor, 3)?),
+                None => None,
+            };
+            let uvs = match primitive.attributes.get("TEXCOORD_0") {
+                Some(accessor) => Some(self.read_accessor(*accessor, 2)?),
+                None => None,
+            };
+
+            let vertex_count = positions.len();
+            let attributes = [("normals", &normals), ("uvs", &uvs)];
+            for (name, attribute) in attributes {
+                match attribute {
+                    Some(values) if values.len() != vertex_count => {
+                        return Err(format!(
+                            "mesh {} has {} {} but {} positions",
+                            index,
+                            values.len(),
+                            name,
+                            vertex_count
+                        ));
+                    }
+                    _ => (),
+                }
+            }
+            let indices: Vec<usize> = match primitive.indices {
+                Some(accessor) => self
+                    .read_accessor(accessor, 1)?
+                    .into_iter()
+                    .map(|i| i[0] as usize)
+                    .collect(),
+                None => (0..vertex_count).collect(),
+            };
+            if let Some(i) = indices.iter().find(|i| **i >= vertex_count) {
+                return Err(format!(
+                    "mesh {} has index {} but only {} vertices",
+                    index, i, vertex_count
+                ));
+            }
+
+            let material = match primitive.material {
+                Some(m) if m >= self.materials.len() => {
+                    return Err(format!("material {} does not exist", m))
+                }
+                _ => *materials.entry(primitive.material).or_insert_with(|| {
+                    mesh.add_material(match primitive.material {
+                        Some(m) => self.materials[m],
+                        None => to_material(&GltfMaterial::default()),
+                    })
+                }),
+            };
+
+            let base = mesh.positions().len();
+            for p in positions.iter() {
+                mesh.add_position(transform.point(Point3::new(p[0], p[1], p[2])));
+            }
+            let normal_base = mesh.normals().len();
+            if let Some(normals) = &normals {
+                for n in normals.iter() {
+                    mesh.add_normal(transform.normal(Vec3::new(n[0], n[1], n[2])));
+                }
+            }
+            let uv_base = mesh.uvs().len();
+            if let Some(uvs) = &uvs {
+                for t in uvs.iter() {
+                    mesh.add_uv((t[0], t[1]));
+                }
+            }
+
+            for mut triangle in triangles(&indices, primitive.mode) {
+                if flips_winding {
+                    triangle.swap(1, 2);
+                }
+
+                let mut face = Face::new(triangle.map(|i| base + i), material);
+                if normals.is_some() {
+                    face.normals = Some(triangle.map(|i| normal_base + i));
+                }
+                if uvs.is_some() {
+                    face.uvs = Some(triangle.map(|i| uv_base + i));
+                }
+                mesh.add_face(face).map_err(|e| e.to_string())?;
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    // Reads an accessor as rows of f64, normalized integers are mapped to
+    // [0, 1] or [-1, 1] as the specification requires.
+    fn read_accessor(&self, index: usize, components: usize) -> Result<Vec<Vec<f64>>, String> {
+        let accessor = self
+            .document
+            .accessors
+            .get(index)
+            .ok_or_else(|| format!("accessor {} does not exist", index))?;
+
+        if accessor.sparse.is_some() {
+            return Err(format!(
+                "accessor {} is sparse, which is not supported",
+                index
+            ));
+        }
+
+        let expected = match components {
+            1 => "SCALAR",
+            2 => "VEC2",
+            _ => "VEC3",
+        };
+        if accessor.kind != expected {
+            return Err(format!(
+                "accessor {} is {}, expected {}",
+                index, accessor.kind, expected
+            ));
+        }
+
+        let component_size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => return Err(format!("accessor {} has component type {}", index, other)),
+        };
+
+        let view_index = match accessor.buffer_view {
+            Some(view) => view,
+            // Accessors without a buffer view are all zeros.
+            None => return Ok(vec![vec![0.0; components]; accessor.count]),
+        };
+        let view = self
+            .document
+            .buffer_views
+            .get(view_index)
+            .ok_or_else(|| format!("buffer view {} does not exist", view_index))?;
+        let buffer = self
+            .buffers
+            .get(view.buffer)
+            .ok_or_else(|| format!("buffer {} does not exist", view.buffer))?;
+
+        let element_size = component_size * components;
+        let stride = view.byte_stride.unwrap_or(element_size);
+        let start = view.byte_offset + accessor.byte_offset;
+        let end = match accessor.count {
+            0 => start,
+            count => start + stride * (count - 1) + element_size,
+        };
+        if end > view.byte_offset + view.byte_length || end > buffer.len() {
+            return Err(format!(
+                "accessor {} reads past the end of its buffer",
+                index
+            ));
+        }
+
+        let mut rows = Vec::with_capacity(accessor.count);
+        for element in 0..accessor.count {
+            let offset = start + element * stride;
+            let row = (0..components)
+                .map(|c| {
+                    let b = &buffer[offset + c * component_size..];
+                    let value = match accessor.component_type {
+                        5120 => b[0] as i8 as f64,
+                        5121 => b[0] as f64,
+                        5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+                        5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+                        5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                        _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    };
+                    match (accessor.normalized, accessor.component_type) {
+                        (true, 5120) => f64::max(value / 127.0, -1.0),
+                        (true, 5121) => value / 255.0,
+                        (true, 5122) => f64::max(value / 32767.0, -1.0),
+                        (true, 5123) => value / 65535.0,
+                        _ => value,
+                    }
+                })
+                .collect();
+            rows.push(row);
+        }
+
+        Ok(rows)
+    }
+}
+
+fn triangles(indices: &[usize], mode: u32) -> Vec<[usize; 3]> {
+    match mode {
+        5 => (2..indices.len())
+            .map(|i| match i % 2 {
+                0 => [indices[i - 2], indices[i - 1], indices[i]],
+                _ => [indices[i - 1], indices[i - 2], indices[i]],
+            })
+            .collect(),
+        6 => (2..indices.len())
+            .map(|i| [indices[0], indices[i - 1], indices[i]])
+            .collect(),
+        _ => indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect(),
+    }
+}
+
+// The subset of the glTF schema that is used.
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Document {
+    scene: Option<usize>,
+    scenes: Vec<GltfSceneDesc>,
+    nodes: Vec<Node>,
+    meshes: Vec<GltfMeshDesc>,
+    materials: Vec<GltfMaterial>,
+    cameras: Vec<GltfCameraDesc>,
+    accessors: Vec<Accessor>,
+    buffer_views: Vec<BufferView>,
+    buffers: Vec<Buffer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GltfSceneDesc {
+    nodes: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct Node {
+    name: Option<String>,
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    camera: Option<usize>,
+    matrix: Option<[f64; 16]>,
+    translation: [f64; 3],
+    rotation: [f64; 4],
+    scale: [f64; 3],
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node {
+            name: None,
+            children: Vec::new(),
+            mesh: None,
+            camera: None,
+            matrix: None,
+            translation: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            scale: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl Node {
+    // None if the node collapses its contents, e.g. with a zero scale.
+    fn local_transform(&self) -> Option<Transform> {
+        match self.matrix {
+            Some(m) => Transform::from_matrix(Matrix4::from_column_major(&m)),
+            None if self.scale.contains(&0.0) => None,
+            None => {
+                let [tx, ty, tz] = self.translation;
+                let [sx, sy, sz] = self.scale;
+                Some(
+                    Transform::translation(Vec3::new(tx, ty, tz))
+                        * Transform::quaternion(self.rotation)
+                        * Transform::scaling(Vec3::new(sx, sy, sz)),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GltfMeshDesc {
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    mode: u32,
+}
+
+impl Default for Primitive {
+    fn default() -> Self {
+        Primitive {
+            attributes: HashMap::new(),
+            indices: None,
+            material: None,
+            mode: 4,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct GltfMaterial {
+    pbr_metallic_roughness: PbrMetallicRoughness,
+    emissive_factor: [f64; 3],
+    alpha_mode: String,
+    extensions: MaterialExtensions,
+}
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        GltfMaterial {
+            pbr_metallic_roughness: PbrMetallicRoughness::default(),
+            emissive_factor: [0.0, 0.0, 0.0],
+            alpha_mode: String::from("OPAQUE"),
+            extensions: MaterialExtensions::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PbrMetallicRoughness {
+    base_color_factor: [f64; 4],
+    metallic_factor: f64,
+    roughness_factor: f64,
+}
+
+impl Default for PbrMetallicRoughness {
+    fn default() -> Self {
+        PbrMetallicRoughness {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct MaterialExtensions {
+    #[serde(rename = "KHR_materials_transmission")]
+    khr_materials_transmission: Option<Transmission>,
+    #[serde(rename = "KHR_materials_ior")]
+    khr_materials_ior: Option<Ior>,
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    khr_materials_emissive_strength: Option<EmissiveStrength>,
+    #[serde(rename = "KHR_materials_volume")]
+    khr_materials_volume: Option<Volume>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Transmission {
+    #[serde(default)]
+    transmission_factor: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ior {
+    #[serde(default = "default_ior")]
+    ior: f64,
+}
+
+fn default_ior() -> f64 {
+    1.5
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EmissiveStrength {
+    #[serde(default = "default_emissive_strength")]
+    emissive_strength: f64,
+}
+
+fn default_emissive_strength() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Volume {
+    attenuation_distance: f64,
+    attenuation_color: [f64; 3],
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume {
+            attenuation_distance: f64::INFINITY,
+            attenuation_color: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GltfCameraDesc {
+    perspective: Option<Perspective>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Perspective {
+    yfov: f64,
+    aspect_ratio: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One triangle (positions as f32, u16 indices) under a translated parent
+    // node, and a camera rotated a quarter turn about y.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [ { "nodes": [0, 2] } ],
+        "nodes": [
+            { "name": "parent", "translation": [0, 0, -5], "children": [1] },
+            { "name": "triangle", "mesh": 0, "scale": [2, 2, 2] },
+            { "name": "camera", "camera": 0, "rotation": [0, 0.7071067811865476, 0, 0.7071067811865476] }
+        ],
+        "meshes": [ { "primitives": [ { "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 } ] } ],
+        "materials": [ { "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 } } ],
+        "cameras": [ { "type": "perspective", "perspective": { "yfov": 0.9272952180016122, "znear": 0.1 } } ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "buffers": [ { "byteLength": 42, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIA" } ]
+    }"#;
+
+    #[test]
+    fn parse_gltf_triangle() {
+        let scene = parse_gltf(TRIANGLE.as_bytes(), "triangle.gltf", None).unwrap();
+
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0].mesh;
+        assert_eq!(scene.meshes[0].name, "triangle");
+        assert_eq!(mesh.faces().len(), 1);
+        assert_eq!(
+            mesh.positions(),
+            &[
+                Point3::new(0.0, 0.0, -5.0),
+                Point3::new(2.0, 0.0, -5.0),
+                Point3::new(0.0, 2.0, -5.0)
+            ]
+        );
+        assert_eq!(
+            mesh.materials(),
+            &[Material::Diffuse(Color::new(1.0, 0.0, 0.0))]
+        );
+
+        assert_eq!(scene.cameras.len(), 1);
+        let camera = &scene.cameras[0];
+        assert!((camera.look_at - camera.position).nearly_eq(Vec3::new(-1.0, 0.0, 0.0)));
+        assert!(camera.world_up.nearly_eq(Vec3::new(0.0, 1.0, 0.0)));
+        assert!(f64::abs(camera.focal_length - 1.0) < 1e-12);
+        assert_eq!(camera.aspect_ratio, None);
+    }
+
+    #[test]
+    fn parse_glb_triangle() {
+        let mut json = TRIANGLE.as_bytes().to_vec();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(12 + 8 + json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&0x4E4F534Au32.to_le_bytes());
+        glb.extend_from_slice(&json);
+
+        let scene = parse_gltf(&glb, "triangle.glb", None).unwrap();
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.cameras.len(), 1);
+    }
+
+    #[test]
+    fn mismatched_attributes() {
+        let text = TRIANGLE
+            .replace(
+                r#""attributes": { "POSITION": 0 }"#,
+                r#""attributes": { "POSITION": 0, "NORMAL": 2 }"#,
+            )
+            .replace(
+                r#""count": 3, "type": "SCALAR" }"#,
+                r#""count": 3, "type": "SCALAR" },
+            { "bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3" }"#,
+            );
+        let error = parse_gltf(text.as_bytes(), "triangle.gltf", None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("mesh 0 has 1 normals but 3 positions"));
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+        assert_eq!(decode_base64("TQ==").unwrap(), b"M");
+        assert!(decode_base64("T*==").is_err());
+    }
+
+    #[test]
+    fn emissive_materials() {
+        let material: GltfMaterial = serde_json::from_str(
+            r#"{ "emissiveFactor": [1, 0.5, 0],
+                 "extensions": { "KHR_materials_emissive_strength": { "emissiveStrength": 8 } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_material(&material),
+            Material::Emissive(Color::new(1.0, 0.5, 0.0), 8.0)
+        );
+
+        let material: GltfMaterial =
+            serde_json::from_str(r#"{ "emissiveFactor": [0, 0, 0] }"#).unwrap();
+        assert!(matches!(to_material(&material), Material::Metalic(..)));
+    }
+
+    #[test]
+    fn glass_materials() {
+        let material: GltfMaterial = serde_json::from_str(
+            r#"{ "pbrMetallicRoughness": { "roughnessFactor": 0 },
+                 "extensions": { "KHR_materials_transmission": { "transmissionFactor": 1 } } }"#,
+        )
+        .unwrap();
+        assert_eq!(to_material(&material), Material::Dielectric(1.5));
+
+        let material: GltfMaterial = serde_json::from_str(
+            r#"{ "pbrMetallicRoughness": { "roughnessFactor": 0.3 },
+                 "extensions": { "KHR_materials_transmission": { "transmissionFactor": 1 },
+                                 "KHR_materials_ior": { "ior": 1.4 },
+                                 "KHR_materials_volume": { "attenuationDistance": 2,
+                                                           "attenuationColor": [1, 0.5, 0.5] } } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            to_material(&material),
+            Material::Glass {
+                refraction_index: 1.4,
+                roughness: 0.3,
+                color: Color::new(1.0, 0.5, 0.5),
+                distance: 2.0,
+            }
+        );
+    }
+}
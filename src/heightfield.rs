@@ -0,0 +1,413 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::triangle::*;
+use super::vec3::*;
+
+// Terrain from a grid of heights, laid over the xz plane from corner and
+// stretched to extent. Heights are scaled by extent.y, images give heights
+// from 0 to 1. Each grid cell is two triangles which are intersected while
+// walking the cells under the ray, normals are interpolated between the
+// grid points.
+
+#[derive(Debug)]
+pub struct HeightfieldError {
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for HeightfieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+impl std::error::Error for HeightfieldError {}
+
+// File a heightfield was loaded from, columns is given for raw floats.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightfieldSource {
+    pub file: String,
+    pub columns: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Heightfield {
+    columns: usize,
+    rows: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    corner: Point3,
+    extent: Vec3,
+    material: Material,
+    bounding_box: Aabb,
+    source: Option<HeightfieldSource>,
+}
+
+impl Heightfield {
+    // heights are row by row along x, rows go along z. None if the grid is
+    // smaller than 2 by 2 or heights doesn't fill it.
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        heights: Vec<f64>,
+        corner: Point3,
+        extent: Vec3,
+        material: Material,
+    ) -> Option<Heightfield> {
+        if columns < 2 || rows < 2 || heights.len() != columns * rows {
+            return None;
+        }
+
+        let mut field = Heightfield {
+            columns,
+            rows,
+            heights,
+            normals: Vec::new(),
+            corner,
+            extent,
+            material,
+            bounding_box: Aabb::empty(),
+            source: None,
+        };
+
+        field.bounding_box = Aabb::from_points(
+            (0..rows)
+                .flat_map(|z| (0..columns).map(move |x| (x, z)))
+                .map(|(x, z)| field.point(x, z)),
+        );
+        field.normals = (0..rows)
+            .flat_map(|z| (0..columns).map(move |x| (x, z)))
+            .map(|(x, z)| field.grid_normal(x, z))
+            .collect();
+
+        Some(field)
+    }
+
+    // 8 or 16 bit grayscale, colors are converted to gray.
+    pub fn from_image<P: AsRef<Path>>(
+        path: P,
+        corner: Point3,
+        extent: Vec3,
+        material: Material,
+    ) -> Result<Heightfield, HeightfieldError> {
+        let path = path.as_ref();
+        let error = |message: String| HeightfieldError {
+            file: path.display().to_string(),
+            message,
+        };
+
+        let image = image::open(path)
+            .map_err(|e| error(e.to_string()))?
+            .into_luma16();
+        let (columns, rows) = (image.width() as usize, image.height() as usize);
+        let heights = image
+            .pixels()
+            .map(|p| p.0[0] as f64 / u16::MAX as f64)
+            .collect();
+
+        Heightfield::new(columns, rows, heights, corner, extent, material).ok_or_else(|| {
+            error(format!(
+                "{}x{} is too small for a heightfield",
+                columns, rows
+            ))
+        })
+    }
+
+    // Little endian 32 bit floats, row by row.
+    pub fn from_raw<P: AsRef<Path>>(
+        path: P,
+        columns: usize,
+        corner: Point3,
+        extent: Vec3,
+        material: Material,
+    ) -> Result<Heightfield, HeightfieldError> {
+        let path = path.as_ref();
+        let error = |message: String| HeightfieldError {
+            file: path.display().to_string(),
+            message,
+        };
+
+        let bytes = fs::read(path).map_err(|e| error(e.to_string()))?;
+        if bytes.len() % 4 != 0 {
+            return Err(error(format!(
+                "{} bytes is not a whole number of floats",
+                bytes.len()
+            )));
+        }
+
+        let heights: Vec<f64> = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        if let Some(index) = heights.iter().position(|h| !h.is_finite()) {
+            return Err(error(format!("height {} is {}", index, heights[index])));
+        }
+        let count = heights.len();
+        let rows = count.checked_div(columns).unwrap_or(0);
+
+        Heightfield::new(columns, rows, heights, corner, extent, material).ok_or_else(|| {
+            error(format!(
+                "{} heights don't make a grid of {} columns with at least 2 rows",
+                count, columns
+            ))
+        })
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn corner(&self) -> Point3 {
+        self.corner
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.extent
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn source(&self) -> Option<&HeightfieldSource> {
+        self.source.as_ref()
+    }
+
+    pub fn set_source(&mut self, source: HeightfieldSource) {
+        self.source = Some(source);
+    }
+
+    pub fn height(&self, x: usize, z: usize) -> f64 {
+        self.heights[z * self.columns + x]
+    }
+
+    fn point(&self, x: usize, z: usize) -> Point3 {
+        self.corner
+            + Vec3::new(
+                x as f64 * self.cell_size().0,
+                self.height(x, z) * self.extent.y,
+                z as f64 * self.cell_size().1,
+            )
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.extent.x / (self.columns - 1) as f64,
+            self.extent.z / (self.rows - 1) as f64,
+        )
+    }
+
+    // From the slopes to the neighbours, one sided at the borders.
+    fn grid_normal(&self, x: usize, z: usize) -> Vec3 {
+        let (x0, x1) = (x.saturating_sub(1), usize::min(x + 1, self.columns - 1));
+        let (z0, z1) = (z.saturating_sub(1), usize::min(z + 1, self.rows - 1));
+        let (dx, dz) = self.cell_size();
+
+        let slope_x =
+            (self.height(x1, z) - self.height(x0, z)) * self.extent.y / ((x1 - x0) as f64 * dx);
+        let slope_z =
+            (self.height(x, z1) - self.height(x, z0)) * self.extent.y / ((z1 - z0) as f64 * dz);
+        Vec3::new(-slope_x, 1.0, -slope_z).unit()
+    }
+
+    fn hit_cell(&self, x: usize, z: usize, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let corners = [(x, z), (x + 1, z + 1), (x + 1, z), (x, z + 1)];
+        let [a, b, c, d] = corners.map(|(x, z)| self.point(x, z));
+        let [na, nb, nc, nd] = corners.map(|(x, z)| self.normals[z * self.columns + x]);
+
+        // Both triangles wind upwards.
+        let mut closest = None;
+        let mut maximum = maximum;
+        for (b, c, nb, nc) in [(b, c, nb, nc), (d, b, nd, nb)] {
+            if let Some((solution, u, v)) = intersect_triangle(ray, a, b, c, minimum, maximum) {
+                maximum = solution;
+                let geometric_normal = (b - a).cross(c - a).unit();
+                let normal = (na * (1.0 - u - v) + nb * u + nc * v).unit();
+                closest = Some((solution, geometric_normal, normal));
+            }
+        }
+
+        let (solution, geometric_normal, mut normal) = closest?;
+        let front = ray.direction.dot(geometric_normal) < 0.0;
+
+        // Keep the shading normal on the same side as the geometry.
+        if normal.dot(geometric_normal) < 0.0 {
+            normal = -normal;
+        }
+
+        let point = ray.at(solution);
+        let mut hit = Hit::new(point, normal, solution, front, self.material);
+        hit.uv = (
+            (point.x - self.corner.x) / self.extent.x,
+            (point.z - self.corner.z) / self.extent.z,
+        );
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let inverse_direction = Vec3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let (entry, exit) =
+            self.bounding_box
+                .clip(ray.origin, inverse_direction, minimum, maximum)?;
+
+        // Walk the cells in grid coordinates, where cells are 1 wide.
+        let (dx, dz) = self.cell_size();
+        let start = ray.at(entry) - self.corner;
+        let cells = [self.columns - 1, self.rows - 1];
+        let position = [start.x / dx, start.z / dz];
+        let direction = [ray.direction.x / dx, ray.direction.z / dz];
+
+        let mut cell = [0; 2];
+        let mut step = [0isize; 2];
+        let mut next = [f64::INFINITY; 2];
+        let mut delta = [f64::INFINITY; 2];
+        for axis in 0..2 {
+            cell[axis] = f64::clamp(position[axis].floor(), 0.0, (cells[axis] - 1) as f64) as usize;
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                delta[axis] = 1.0 / direction[axis];
+                next[axis] = entry + (cell[axis] as f64 + 1.0 - position[axis]) * delta[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                delta[axis] = -1.0 / direction[axis];
+                next[axis] = entry + (position[axis] - cell[axis] as f64) * delta[axis];
+            }
+        }
+
+        loop {
+            if let Some(hit) = self.hit_cell(cell[0], cell[1], ray, minimum, maximum) {
+                return Some(hit);
+            }
+
+            let axis = if next[0] < next[1] { 0 } else { 1 };
+            if next[axis] > exit {
+                return None;
+            }
+
+            match cell[axis].checked_add_signed(step[axis]) {
+                Some(c) if c < cells[axis] => cell[axis] = c,
+                _ => return None,
+            }
+            next[axis] += delta[axis];
+        }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounding_box)
+    }
+}
+
+#[test]
+fn hit_heightfield() {
+    // A ridge along z at x = 1.
+    let heights = vec![0.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+    let material = Material::Dielectric(1.5);
+    let field = Heightfield::new(
+        3,
+        2,
+        heights,
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(2.0, 2.0, 1.0),
+        material,
+    )
+    .unwrap();
+    assert_eq!(
+        field.bounding_box(),
+        Some(Aabb::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 2.0, 1.0)
+        ))
+    );
+
+    let ray = Ray::new(Point3::new(0.5, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+    let hit = field.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert!((hit.point.y - 1.0).abs() < 1e-9);
+    assert_eq!(hit.uv, (0.25, 0.5));
+
+    // Smooth normals lean less than the slope towards the ridge.
+    let slope = Vec3::new(-2.0, 1.0, 0.0).unit();
+    assert!(hit.normal.y > slope.y);
+    let ray = Ray::new(Point3::new(0.999, 5.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+    let hit = field.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.normal.y > 0.99);
+
+    // Walking across cells, the far side of the ridge is hidden.
+    let ray = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+    let hit = field.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!((hit.point.x - 0.25).abs() < 1e-9);
+    let ray = Ray::new(Point3::new(3.0, 0.5, 0.2), Vec3::new(-1.0, 0.0, 0.0));
+    let hit = field.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!((hit.point.x - 1.75).abs() < 1e-9);
+
+    let ray = Ray::new(Point3::new(-1.0, 2.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+    assert!(field.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+    assert!(Heightfield::new(
+        1,
+        2,
+        vec![0.0, 0.0],
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 1.0),
+        material
+    )
+    .is_none());
+}
+
+#[test]
+fn load_heightfield_files() {
+    let directory = std::env::temp_dir();
+    let corner = Point3::new(0.0, 0.0, 0.0);
+    let extent = Vec3::new(1.0, 10.0, 1.0);
+    let material = Material::Dielectric(1.5);
+
+    let png = directory.join("raycast_heightfield.png");
+    let pixels: Vec<u16> = vec![0, u16::MAX, 0, u16::MAX / 2, 0, 0];
+    image::ImageBuffer::<image::Luma<u16>, _>::from_raw(3, 2, pixels)
+        .unwrap()
+        .save(&png)
+        .unwrap();
+    let field = Heightfield::from_image(&png, corner, extent, material).unwrap();
+    assert_eq!((field.columns(), field.rows()), (3, 2));
+    assert_eq!(field.height(1, 0), 1.0);
+    assert!((field.height(0, 1) - 0.5).abs() < 1e-4);
+    assert_eq!(field.bounding_box().unwrap().maximum.y, 10.0);
+
+    let raw = directory.join("raycast_heightfield.r32");
+    let heights = [1.5f32, -2.0, 0.0, 4.0];
+    let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+    fs::write(&raw, bytes).unwrap();
+    let field = Heightfield::from_raw(&raw, 2, corner, extent, material).unwrap();
+    assert_eq!(field.height(1, 1), 4.0);
+    assert_eq!(field.bounding_box().unwrap().minimum.y, -20.0);
+
+    let error = Heightfield::from_raw(&raw, 3, corner, extent, material).unwrap_err();
+    assert!(error.message.contains("3 columns"));
+
+    let heights = [1.5f32, f32::NAN, 0.0, 4.0];
+    let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+    fs::write(&raw, bytes).unwrap();
+    let error = Heightfield::from_raw(&raw, 2, corner, extent, material).unwrap_err();
+    assert_eq!(error.message, "height 1 is NaN");
+}
@@ -1,14 +1,18 @@
+use std::any::Any;
+
+use super::aabb::*;
 use super::material::*;
 use super::ray::*;
 use super::vec3::*;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Hit {
     pub point: Point3,
     pub normal: Vec3,
     pub solution: f64,
     pub front: bool,
     pub material: Material,
+    pub uv: (f64, f64),
 }
 
 impl Hit {
@@ -19,10 +23,52 @@ impl Hit {
             solution,
             front,
             material,
+            uv: (0.0, 0.0),
         }
     }
 }
 
-pub trait Hittable {
+// Any lets a World hand out the concrete type of its objects.
+pub trait Hittable: Any {
     fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit>;
+
+    // None for unbounded objects, which are then tested against every ray.
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    // Every stretch of the whole ray, not only of minimum..maximum, inside
+    // the object in order along the ray. None for objects that don't enclose
+    // a volume, constructive solid geometry needs them closed.
+    fn spans(&self, _ray: &Ray) -> Option<Vec<Span>> {
+        None
+    }
+}
+
+// The ray enters the object at enter and leaves it at exit, the normals of
+// both face against the ray.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub enter: Hit,
+    pub exit: Hit,
+}
+
+// The span of a convex object is between its nearest and its farthest hit,
+// the farthest is the nearest hit of the reversed ray.
+pub fn convex_spans<H: Hittable + ?Sized>(object: &H, ray: &Ray) -> Vec<Span> {
+    let mut enter = match object.hit(ray, f64::NEG_INFINITY, f64::INFINITY) {
+        Some(hit) => hit,
+        None => return Vec::new(),
+    };
+    let reversed = Ray::with_time(ray.origin, -ray.direction, ray.time);
+    let mut exit = match object.hit(&reversed, f64::NEG_INFINITY, f64::INFINITY) {
+        Some(hit) => hit,
+        None => return Vec::new(),
+    };
+
+    enter.front = true;
+    exit.solution = -exit.solution;
+    exit.normal = -exit.normal;
+    exit.front = false;
+    vec![Span { enter, exit }]
 }
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use super::aabb::*;
+use super::hittable::*;
+use super::ray::*;
+use super::transform::*;
+
+// An object placed into the world by a transform. The object is shared, so
+// placing a mesh many times stores its triangles and BVH only once and the
+// World's BVH over the instances sits on top of the mesh BVHs.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hittable + Send + Sync>,
+    transform: Transform,
+    bounding_box: Option<Aabb>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable + Send + Sync>, transform: Transform) -> Instance {
+        let bounding_box = object.bounding_box().map(|b| transform.aabb(&b));
+        Instance {
+            object,
+            transform,
+            bounding_box,
+        }
+    }
+
+    pub fn object(&self) -> &Arc<dyn Hittable + Send + Sync> {
+        &self.object
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        hit_transformed(&*self.object, &self.transform, ray, minimum, maximum)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        spans_transformed(&*self.object, &self.transform, ray)
+    }
+}
+
+// An object of its own moved, rotated or scaled by a transform.
+#[derive(Clone, Debug)]
+pub struct Transformed<H: Hittable> {
+    object: H,
+    transform: Transform,
+    bounding_box: Option<Aabb>,
+}
+
+impl<H: Hittable> Transformed<H> {
+    pub fn new(object: H, transform: Transform) -> Transformed<H> {
+        let bounding_box = object.bounding_box().map(|b| transform.aabb(&b));
+        Transformed {
+            object,
+            transform,
+            bounding_box,
+        }
+    }
+
+    pub fn object(&self) -> &H {
+        &self.object
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        hit_transformed(&self.object, &self.transform, ray, minimum, maximum)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        spans_transformed(&self.object, &self.transform, ray)
+    }
+}
+
+// An object moving between two poses while the camera shutter is open.
+#[derive(Clone, Debug)]
+pub struct Animated<H: Hittable> {
+    object: H,
+    transform: AnimatedTransform,
+    bounding_box: Option<Aabb>,
+}
+
+impl<H: Hittable> Animated<H> {
+    pub fn new(object: H, transform: AnimatedTransform) -> Animated<H> {
+        let bounding_box = object.bounding_box().map(|b| transform.aabb(&b));
+        Animated {
+            object,
+            transform,
+            bounding_box,
+        }
+    }
+
+    pub fn object(&self) -> &H {
+        &self.object
+    }
+
+    pub fn transform(&self) -> &AnimatedTransform {
+        &self.transform
+    }
+}
+
+impl<H: Hittable> Hittable for Animated<H> {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let transform = self.transform.at(ray.time);
+        hit_transformed(&self.object, &transform, ray, minimum, maximum)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        spans_transformed(&self.object, &self.transform.at(ray.time), ray)
+    }
+}
+
+fn hit_transformed(
+    object: &dyn Hittable,
+    transform: &Transform,
+    ray: &Ray,
+    minimum: f64,
+    maximum: f64,
+) -> Option<Hit> {
+    let hit = object.hit(&local_ray(transform, ray), minimum, maximum)?;
+    Some(to_world(transform, ray, hit))
+}
+
+fn spans_transformed(object: &dyn Hittable, transform: &Transform, ray: &Ray) -> Option<Vec<Span>> {
+    let spans = object.spans(&local_ray(transform, ray))?;
+    Some(
+        spans
+            .into_iter()
+            .map(|span| Span {
+                enter: to_world(transform, ray, span.enter),
+                exit: to_world(transform, ray, span.exit),
+            })
+            .collect(),
+    )
+}
+
+// The direction is not normalized so solutions are the same in both spaces.
+fn local_ray(transform: &Transform, ray: &Ray) -> Ray {
+    let inverse = transform.inverse();
+    Ray::with_time(
+        inverse.point(ray.origin),
+        inverse.vector(ray.direction),
+        ray.time,
+    )
+}
+
+fn to_world(transform: &Transform, ray: &Ray, mut hit: Hit) -> Hit {
+    hit.point = ray.at(hit.solution);
+    hit.normal = transform.normal(hit.normal).unit();
+    hit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cuboid::*;
+    use crate::material::*;
+    use crate::mesh::*;
+    use crate::sphere::*;
+    use crate::vec3::*;
+    use crate::world::*;
+
+    #[test]
+    fn shared_instances() {
+        let square = Mesh::from_triangles(
+            vec![
+                Point3::new(-0.5, -0.5, 0.0),
+                Point3::new(0.5, -0.5, 0.0),
+                Point3::new(0.5, 0.5, 0.0),
+                Point3::new(-0.5, 0.5, 0.0),
+            ],
+            &[[0, 1, 2], [0, 2, 3]],
+            Material::Diffuse(Color::new(0.5, 0.5, 0.5)),
+        )
+        .unwrap();
+        let square: Arc<dyn Hittable + Send + Sync> = Arc::new(square);
+
+        let mut world = World::new();
+        for i in 0..100 {
+            let transform = Transform::rotation(Vec3::new(0.0, 1.0, 0.0), PI / 2.0)
+                .then(&Transform::translation(Vec3::new(i as f64 * 2.0, 0.0, 0.0)));
+            world.add(Instance::new(square.clone(), transform));
+        }
+        assert_eq!(Arc::strong_count(&square), 101);
+        assert_eq!(world.bvh_stats().primitive_count, 100);
+
+        // The squares are rotated to face +x, the ray passes the first three.
+        let ray = Ray::new(Point3::new(-5.0, 0.25, 0.0), Vec3::new(2.0, 0.0, 0.0));
+        let hit = world.test_camera_ray(&ray).unwrap();
+        assert!((hit.solution - 2.5).abs() < 1e-9);
+        assert!(hit.point.nearly_eq(Point3::new(0.0, 0.25, 0.0)));
+        assert!(hit.normal.nearly_eq(Vec3::new(-1.0, 0.0, 0.0)));
+
+        let ray = Ray::new(Point3::new(5.0, 0.25, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let hit = world.test_camera_ray(&ray).unwrap();
+        assert!(hit.point.nearly_eq(Point3::new(4.0, 0.25, 0.0)));
+
+        let ray = Ray::new(Point3::new(5.0, 0.75, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(world.test_camera_ray(&ray).is_none());
+    }
+
+    #[test]
+    fn scaled_sphere() {
+        // A unit sphere squashed into an ellipsoid and moved up.
+        let ellipsoid = Transformed::new(
+            Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Material::Dielectric(1.5)),
+            Transform::translation(Vec3::new(0.0, 1.0, 0.0))
+                * Transform::scaling(Vec3::new(2.0, 0.5, 1.0)),
+        );
+        assert_eq!(
+            ellipsoid.bounding_box(),
+            Some(Aabb::new(
+                Point3::new(-2.0, 0.5, -1.0),
+                Point3::new(2.0, 1.5, 1.0)
+            ))
+        );
+
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = ellipsoid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.solution - 3.5).abs() < 1e-9);
+        assert!(hit.normal.nearly_eq(Vec3::new(0.0, 1.0, 0.0)));
+
+        let ray = Ray::new(Point3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let hit = ellipsoid.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.point.nearly_eq(Point3::new(2.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn animated_box() {
+        let start = Pose::identity();
+        let end = Pose {
+            translation: Vec3::new(4.0, 0.0, 0.0),
+            ..Pose::identity()
+        };
+        let sliding = Animated::new(
+            Cuboid::new(
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(1.0, 1.0, 1.0),
+                Material::Dielectric(1.5),
+            ),
+            AnimatedTransform::new(start, end, 0.0, 1.0),
+        );
+        assert_eq!(
+            sliding.bounding_box(),
+            Some(Aabb::new(
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(5.0, 1.0, 1.0)
+            ))
+        );
+
+        let direction = Vec3::new(0.0, -1.0, 0.0);
+        let ray = Ray::with_time(Point3::new(4.0, 5.0, 0.0), direction, 0.0);
+        assert!(sliding.hit(&ray, 0.0, f64::INFINITY).is_none());
+        let ray = Ray::with_time(Point3::new(4.0, 5.0, 0.0), direction, 0.9);
+        let hit = sliding.hit(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!((hit.solution - 4.0).abs() < 1e-9);
+        assert!(sliding.spans(&ray).is_some_and(|spans| spans.len() == 1));
+    }
+}
@@ -1,9 +1,30 @@
+pub mod aabb;
+pub mod bvh;
 pub mod camera;
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod gltf;
+pub mod heightfield;
 pub mod hittable;
+pub mod instance;
+pub mod light;
 pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod plane;
+pub mod ply;
+pub mod quad;
 pub mod ray;
 pub mod renderer;
+pub mod scene;
+pub mod sdf;
 pub mod sphere;
+pub mod stl;
+pub mod transform;
+pub mod triangle;
 pub mod utils;
+pub mod validate;
 pub mod vec3;
 pub mod world;
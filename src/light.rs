@@ -0,0 +1,389 @@
+use rand::prelude::*;
+use std::any::Any;
+
+use super::hittable::*;
+use super::material::*;
+use super::quad::*;
+use super::ray::*;
+use super::sphere::*;
+use super::vec3::*;
+
+// Direction from a point towards a light, how far the light is along it, the
+// light arriving and the solid angle density it was picked with. Lights that
+// scattered rays can't hit, like points and the sun, are only found by
+// sampling them.
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f64,
+    pub emitted: Color,
+    pub pdf: f64,
+    pub hittable: bool,
+}
+
+// Light source that can be sampled for direct lighting. Any lets a world hand
+// out the concrete light.
+pub trait Light: Any + Send + Sync {
+    // None when no part of the light shines towards point.
+    fn sample(&self, point: Point3) -> Option<LightSample>;
+
+    // Density of sample picking direction from point, 0 for directions that
+    // miss the light.
+    fn pdf(&self, point: Point3, direction: Vec3) -> f64;
+}
+
+pub fn is_emissive(material: Material) -> bool {
+    matches!(material, Material::Emissive(..))
+}
+
+// Samples directions uniformly in the cone of directions the sphere covers,
+// seen from outside of it.
+impl Light for Sphere {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let to_center = self.location() - point;
+        let cone = cone_solid_angle(self.radius(), to_center.magnitude2())?;
+
+        let direction = sample_cone(to_center.unit(), cone);
+
+        let hit = self.hit(&Ray::new(point, direction), 0.0, f64::INFINITY)?;
+        Some(LightSample {
+            direction,
+            distance: hit.solution,
+            emitted: hit.material.emitted(&hit),
+            pdf: 1.0 / cone,
+            hittable: true,
+        })
+    }
+
+    fn pdf(&self, point: Point3, direction: Vec3) -> f64 {
+        let to_center = self.location() - point;
+        let cone = match cone_solid_angle(self.radius(), to_center.magnitude2()) {
+            Some(cone) => cone,
+            None => return 0.0,
+        };
+        match self.hit(&Ray::new(point, direction), 0.0, f64::INFINITY) {
+            Some(_) => 1.0 / cone,
+            None => 0.0,
+        }
+    }
+}
+
+// Solid angle of a sphere seen from distance2 away, None from inside.
+fn cone_solid_angle(radius: f64, distance2: f64) -> Option<f64> {
+    let sin2 = radius * radius / distance2;
+    if radius <= 0.0 || sin2 >= 1.0 {
+        return None;
+    }
+    // 1 - cos of the cone's half angle, without losing small cones.
+    let one_minus_cos = sin2 / (1.0 + f64::sqrt(1.0 - sin2));
+    Some(2.0 * PI * one_minus_cos)
+}
+
+// Uniform direction in the cone around the unit axis with the given solid
+// angle.
+fn sample_cone(axis: Vec3, solid_angle: f64) -> Vec3 {
+    let mut rng = thread_rng();
+    let (tangent, bitangent) = axis.orthonormal_basis();
+    let cos_theta = 1.0 - rng.gen::<f64>() * solid_angle / (2.0 * PI);
+    let sin_theta = f64::sqrt(f64::max(0.0, 1.0 - cos_theta * cos_theta));
+    let (sin_phi, cos_phi) = f64::sin_cos(2.0 * PI * rng.gen::<f64>());
+    axis * cos_theta + (tangent * cos_phi + bitangent * sin_phi) * sin_theta
+}
+
+// Samples points uniformly on the quad. Quads only shine from the front.
+impl Light for Quad {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let mut rng = thread_rng();
+        let (u, v) = self.sides();
+        let on_light = self.corner() + u * rng.gen::<f64>() + v * rng.gen::<f64>();
+
+        let to_light = on_light - point;
+        let distance = to_light.magnitude();
+        let direction = to_light / distance;
+        let cosine = -self.normal().dot(direction);
+        if cosine <= 1e-12 {
+            return None;
+        }
+
+        let hit = Hit::new(on_light, -self.normal(), distance, true, self.material());
+        Some(LightSample {
+            direction,
+            distance,
+            emitted: self.material().emitted(&hit),
+            pdf: distance * distance / (cosine * area(self)),
+            hittable: true,
+        })
+    }
+
+    fn pdf(&self, point: Point3, direction: Vec3) -> f64 {
+        let hit = match self.hit(&Ray::new(point, direction), 0.0, f64::INFINITY) {
+            Some(hit) if hit.front => hit,
+            _ => return 0.0,
+        };
+        let distance2 = (hit.point - point).magnitude2();
+        let cosine = -self.normal().dot(direction.unit());
+        match cosine > 1e-12 {
+            true => distance2 / (cosine * area(self)),
+            false => 0.0,
+        }
+    }
+}
+
+fn area(quad: &Quad) -> f64 {
+    let (u, v) = quad.sides();
+    u.cross(v).magnitude()
+}
+
+// Light from a single point, falling off with the square of the distance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointLight {
+    pub position: Point3,
+    pub color: Color,
+    pub intensity: f64,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, color: Color, intensity: f64) -> PointLight {
+        PointLight {
+            position,
+            color,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        point_sample(self.position, point, self.color * self.intensity)
+    }
+
+    fn pdf(&self, _: Point3, _: Vec3) -> f64 {
+        0.0
+    }
+}
+
+fn point_sample(position: Point3, point: Point3, emitted: Color) -> Option<LightSample> {
+    let to_light = position - point;
+    let distance2 = to_light.magnitude2();
+    if distance2 <= 0.0 {
+        return None;
+    }
+    let distance = f64::sqrt(distance2);
+    Some(LightSample {
+        direction: to_light / distance,
+        distance,
+        emitted: emitted / distance2,
+        pdf: 1.0,
+        hittable: false,
+    })
+}
+
+// Point light shining into a cone of cone_angle degrees around direction. The
+// outer softness share of the cone's half angle fades out smoothly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vec3,
+    pub color: Color,
+    pub intensity: f64,
+    pub cone_angle: f64,
+    pub softness: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        direction: Vec3,
+        color: Color,
+        intensity: f64,
+        cone_angle: f64,
+        softness: f64,
+    ) -> SpotLight {
+        SpotLight {
+            position,
+            direction,
+            color,
+            intensity,
+            cone_angle,
+            softness,
+        }
+    }
+
+    // How much of the light goes out towards direction, 1 inside the cone.
+    pub fn falloff(&self, direction: Vec3) -> f64 {
+        let half_angle = self.cone_angle.to_radians() * 0.5;
+        let cos_outer = f64::cos(half_angle);
+        let cos_inner = f64::cos(half_angle * (1.0 - self.softness.clamp(0.0, 1.0)));
+        let cosine = self.direction.unit().dot(direction.unit());
+        if cosine >= cos_inner {
+            return 1.0;
+        }
+        if cosine <= cos_outer {
+            return 0.0;
+        }
+        let t = (cosine - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let falloff = self.falloff(point - self.position);
+        if falloff <= 0.0 {
+            return None;
+        }
+        point_sample(
+            self.position,
+            point,
+            self.color * (self.intensity * falloff),
+        )
+    }
+
+    fn pdf(&self, _: Point3, _: Vec3) -> f64 {
+        0.0
+    }
+}
+
+// Light from far away travelling along direction, like the sun. The intensity
+// is the light arriving on a surface facing it. A sun with an angular diameter
+// in degrees casts soft shadows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub color: Color,
+    pub intensity: f64,
+    pub angular_diameter: f64,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, color: Color, intensity: f64) -> DirectionalLight {
+        DirectionalLight {
+            direction,
+            color,
+            intensity,
+            angular_diameter: 0.0,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _: Point3) -> Option<LightSample> {
+        let towards = -self.direction.unit();
+        let emitted = self.color * self.intensity;
+        let one_minus_cos = 1.0 - f64::cos(self.angular_diameter.to_radians() * 0.5);
+        if one_minus_cos <= 0.0 {
+            return Some(LightSample {
+                direction: towards,
+                distance: f64::INFINITY,
+                emitted,
+                pdf: 1.0,
+                hittable: false,
+            });
+        }
+
+        // Spread evenly over the disk of the sun.
+        let cone = 2.0 * PI * one_minus_cos;
+        Some(LightSample {
+            direction: sample_cone(towards, cone),
+            distance: f64::INFINITY,
+            emitted: emitted / cone,
+            pdf: 1.0 / cone,
+            hittable: false,
+        })
+    }
+
+    fn pdf(&self, _: Point3, _: Vec3) -> f64 {
+        0.0
+    }
+}
+
+// MIS weight of a sample taken with density a when density b could also
+// have taken it.
+pub fn power_heuristic(a: f64, b: f64) -> f64 {
+    let (a2, b2) = (a * a, b * b);
+    match a2 + b2 > 0.0 {
+        true => a2 / (a2 + b2),
+        false => 0.0,
+    }
+}
+
+#[test]
+fn sample_lights() {
+    let lamp = Material::Emissive(Color::new(1.0, 0.5, 0.5), 4.0);
+    let point = Point3::new(0.0, 0.0, 0.0);
+    let sphere = Sphere::new(Point3::new(0.0, 0.0, -4.0), 2.0, lamp);
+    // Seen from the ceiling, facing down.
+    let quad = Quad::new(
+        Point3::new(-1.0, 2.0, -1.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 2.0),
+        lamp,
+    );
+    assert_eq!(quad.normal(), Vec3::new(0.0, -1.0, 0.0));
+
+    // The sphere is 60 degrees across, the quad a square seen from its center.
+    let lights: [(&dyn Light, f64); 2] = [
+        (&sphere, 2.0 * PI * (1.0 - f64::sqrt(3.0) / 2.0)),
+        (&quad, 4.0 * f64::asin(0.2)),
+    ];
+    for (light, expected) in lights {
+        let mut solid_angle = 0.0;
+        for _ in 0..1000 {
+            let sample = light.sample(point).unwrap();
+            assert_eq!(sample.emitted, Color::new(4.0, 2.0, 2.0));
+            let pdf = light.pdf(point, sample.direction * 3.0);
+            assert!((pdf - sample.pdf).abs() < 1e-9 * pdf);
+            solid_angle += 1.0 / sample.pdf / 1000.0;
+        }
+        assert!((solid_angle - expected).abs() < 0.02, "{}", solid_angle);
+    }
+
+    // Nothing from inside the sphere or behind the quad.
+    assert!(sphere.sample(Point3::new(0.0, 0.0, -3.0)).is_none());
+    assert!(quad.sample(Point3::new(0.0, 3.0, 0.0)).is_none());
+    assert_eq!(
+        quad.pdf(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+        0.0
+    );
+    assert_eq!(sphere.pdf(point, Vec3::new(0.0, 1.0, 0.0)), 0.0);
+}
+
+#[test]
+fn punctual_lights() {
+    let white = Color::new(1.0, 1.0, 1.0);
+    let point = Point3::new(0.0, 0.0, 0.0);
+
+    let bulb = PointLight::new(Point3::new(0.0, 2.0, 0.0), white, 8.0);
+    let sample = bulb.sample(point).unwrap();
+    assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!((sample.distance, sample.emitted), (2.0, white * 2.0));
+    assert!(!sample.hittable);
+
+    // 90 degrees wide, fading over the outer half of its half angle.
+    let spot = SpotLight::new(
+        Point3::new(0.0, 2.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        white,
+        8.0,
+        90.0,
+        0.5,
+    );
+    assert_eq!(spot.falloff(Vec3::new(0.1, -1.0, 0.0)), 1.0);
+    assert_eq!(spot.falloff(Vec3::new(1.1, -1.0, 0.0)), 0.0);
+    let edge = spot.falloff(Vec3::new(0.7, -1.0, 0.0));
+    assert!(edge > 0.0 && edge < 1.0);
+    assert_eq!(spot.sample(point).unwrap().emitted, white * 2.0);
+    assert!(spot.sample(Point3::new(5.0, 0.0, 0.0)).is_none());
+
+    let mut sun = DirectionalLight::new(Vec3::new(0.0, -2.0, 0.0), white, 3.0);
+    let sample = sun.sample(point).unwrap();
+    assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(sample.distance, f64::INFINITY);
+    // A wide sun still delivers the same light, spread over its disk.
+    sun.angular_diameter = 10.0;
+    for _ in 0..100 {
+        let sample = sun.sample(point).unwrap();
+        assert!(sample.direction.y >= f64::cos(5f64.to_radians()) - 1e-12);
+        assert!(((sample.emitted / sample.pdf) - white * 3.0).nearly_zero());
+    }
+}
@@ -1,25 +1,129 @@
 use rand::prelude::*;
+use std::path::Path;
 use std::time::Instant;
 
 use raycast::camera::*;
+use raycast::csg::*;
 use raycast::material::*;
+use raycast::plane::*;
 use raycast::renderer::*;
+use raycast::scene::*;
 use raycast::sphere::*;
 use raycast::utils::*;
+use raycast::validate::*;
 use raycast::vec3::*;
 use raycast::world::*;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
+    if args.len() == 3 && args[1] == "check" {
+        check_scene(&args[2]);
+        return;
+    }
+
     if args.len() < 3 {
-        println!("Usage: {} <png file> <image size> ...", args[0]);
+        println!(
+            "Usage: {} <png file> <image size | scene file> ...",
+            args[0]
+        );
+        println!(
+            "       {} <png file> <image size | scene file> <save scene file>",
+            args[0]
+        );
+        println!(
+            "       {} <png file> <gltf file> <width> <samples per pixel> ...",
+            args[0]
+        );
+        println!("       {} check <scene file>", args[0]);
         println!("Example: {} myfile.png 800x600 ...", args[0]);
+        println!("Example: {} myfile.png 800x600 cover.json", args[0]);
+        println!("Example: {} myfile.png data/test.json", args[0]);
+        println!("Example: {} myfile.png model.glb", args[0]);
+        println!("Example: {} myfile.png model.glb 1920 500", args[0]);
         std::process::exit(1);
     }
 
-    let (width, height) = parse_pair::<usize>(&args[2], 'x').expect("Failed to parse image size");
+    // glTF files can be followed by the image width and samples per pixel,
+    // the scene file to save to comes after them.
+    let gltf_settings = match (args.get(3), args.get(4)) {
+        (Some(width), Some(samples)) if is_gltf(&args[2]) => {
+            width.parse::<usize>().ok().zip(samples.parse::<u32>().ok())
+        }
+        _ => None,
+    };
+    let save_index = if gltf_settings.is_some() { 5 } else { 3 };
+
+    let (camera, world, the_renderer) = match parse_pair::<usize>(&args[2], 'x') {
+        Some((width, height)) => cover_scene(width, height),
+        None => match gltf_settings.map_or_else(
+            || Scene::load(&args[2]),
+            |(width, samples)| Scene::load_gltf(&args[2], width, samples),
+        ) {
+            Ok(scene) => (scene.camera, scene.world, scene.renderer),
+            Err(SceneError::Invalid(diagnostics)) => {
+                for diagnostic in diagnostics.iter() {
+                    println!("{}:{}", args[2], diagnostic);
+                }
+                std::process::exit(1);
+            }
+            Err(e) => {
+                println!("Failed to load scene {}: {}", args[2], e);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    if let Some(filename) = args.get(save_index) {
+        let directory = Path::new(filename)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let saved = SceneFile::new(&*camera, &world, &the_renderer, directory)
+            .and_then(|file| file.to_json())
+            .and_then(|json| Ok(std::fs::write(filename, json)?));
+        if let Err(e) = saved {
+            println!("Failed to save scene {}: {}", filename, e);
+            std::process::exit(1);
+        }
+    }
+
+    println!("World BVH: {}", world.bvh_stats());
+
+    let now = Instant::now();
+    let pixels = the_renderer.draw_scene(&*camera, &world);
+    println!("Renderer.draw_scene: {}ms", now.elapsed().as_millis());
+
+    write_image(
+        &args[1],
+        pixels_to_bytes(&pixels),
+        the_renderer.width(),
+        the_renderer.height(),
+    );
+}
+
+fn check_scene(filename: &str) {
+    let text = match std::fs::read_to_string(filename) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("{}: {}", filename, e);
+            std::process::exit(1);
+        }
+    };
+
+    let diagnostics = validate_scene(&text);
+    for diagnostic in diagnostics.iter() {
+        println!("{}:{}", filename, diagnostic);
+    }
+
+    if !diagnostics.is_empty() {
+        println!("{}: {} problem(s) found", filename, diagnostics.len());
+        std::process::exit(1);
+    }
+
+    println!("{}: ok", filename);
+}
 
+fn cover_scene(width: usize, height: usize) -> (Box<dyn CameraModel>, World, Renderer) {
     let aspect_ratio = width as f64 / height as f64;
     let samples_per_pixel: u32 = 500;
     let focal_length = 2.0;
@@ -35,19 +139,17 @@ fn main() {
     let mut world = World::new();
     raytracing_in_one_weekend_cover(&mut world);
 
-    let the_renderer = Renderer::new(width, height, samples_per_pixel);
-
-    let now = Instant::now();
-    let pixels = the_renderer.draw_scene(&camera, &world);
-    println!("Renderer.draw_scene: {}ms", now.elapsed().as_millis());
-
-    write_image(&args[1], pixels_to_bytes(&pixels), width, height);
+    (
+        Box::new(camera),
+        world,
+        Renderer::new(width, height, samples_per_pixel),
+    )
 }
 
 fn raytracing_in_one_weekend_cover(world: &mut World) {
-    world.add_sphere(Sphere::new(
-        Point3::new(0.0, -10000.0, 0.0),
-        10000.0,
+    world.add(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
         Material::Diffuse(Color::new(0.5, 0.5, 0.5)),
     ));
 
@@ -108,15 +210,11 @@ fn random_scene(world: &mut World) {
         Material::Diffuse(Color::new(0.1, 0.8, 0.2)),
     ));
 
-    world.add_sphere(Sphere::new(
+    // Hollow glass spheres.
+    world.add(hollow_sphere(
         Point3::new(0.0, 0.5, -1.0),
         0.5,
-        Material::Dielectric(1.1),
-    ));
-
-    world.add_sphere(Sphere::new(
-        Point3::new(0.0, 0.5, -1.0),
-        -0.45,
+        0.05,
         Material::Dielectric(1.1),
     ));
 
@@ -126,15 +224,10 @@ fn random_scene(world: &mut World) {
         Material::Metalic(Color::new(0.8, 0.6, 0.2), 0.0),
     ));
 
-    world.add_sphere(Sphere::new(
+    world.add(hollow_sphere(
         Point3::new(0.1, 0.15, -0.2),
         0.15,
-        Material::Dielectric(1.5),
-    ));
-
-    world.add_sphere(Sphere::new(
-        Point3::new(0.1, 0.15, -0.2),
-        -0.15,
+        0.01,
         Material::Dielectric(1.5),
     ));
 
@@ -150,3 +243,10 @@ fn random_scene(world: &mut World) {
         Material::Metalic(Color::new(0.9, 0.1, 0.1), 0.2),
     ));
 }
+
+fn hollow_sphere(center: Point3, radius: f64, thickness: f64, material: Material) -> Csg {
+    Csg::difference(
+        Sphere::new(center, radius, material),
+        Sphere::new(center, radius - thickness, material),
+    )
+}
@@ -1,3 +1,5 @@
+use rand::prelude::*;
+
 use super::hittable::*;
 use super::ray::*;
 use super::vec3::*;
@@ -7,60 +9,437 @@ pub enum Material {
     Diffuse(Color),
     Metalic(Color, f64),
     Dielectric(f64),
+    // Dielectric frosted by roughness from 0 to 1, tinting the light inside
+    // it to color over every distance travelled.
+    Glass {
+        refraction_index: f64,
+        roughness: f64,
+        color: Color,
+        distance: f64,
+    },
+    // Light source, it shines with the color times intensity from the front
+    // of the surface and reflects nothing.
+    Emissive(Color, f64),
+}
+
+// Direction sample picked, with the weight eval / pdf for the light coming
+// from it. Delta lobes like mirrors and glass scatter into single directions,
+// their pdf is 0 and eval can't find them.
+#[derive(Copy, Clone, Debug)]
+pub struct ScatterSample {
+    pub direction: Vec3,
+    pub weight: Color,
+    pub pdf: f64,
+    pub delta: bool,
 }
 
+// Directions are unit vectors pointing away from the hit: wo towards where
+// the ray came from and wi towards where the light comes from.
 impl Material {
+    pub fn sample(&self, hit: &Hit, wo: Vec3) -> Option<ScatterSample> {
+        match self {
+            Self::Diffuse(albedo) => diffuse_sample(hit, albedo),
+            Self::Metalic(albedo, fuzziness) => metalic_sample(hit, wo, albedo, fuzziness),
+            Self::Dielectric(refraction_index) => dielectric_sample(hit, wo, *refraction_index),
+            Self::Glass {
+                refraction_index,
+                roughness,
+                ..
+            } => match *roughness > 0.0 {
+                true => rough_dielectric_sample(hit, wo, *refraction_index, *roughness),
+                false => dielectric_sample(hit, wo, *refraction_index),
+            },
+            Self::Emissive(..) => None,
+        }
+    }
+
+    // Share of the light arriving from wi that leaves towards wo, times the
+    // cosine of wi to the normal. Black for delta lobes.
+    pub fn eval(&self, hit: &Hit, wi: Vec3, wo: Vec3) -> Color {
+        match self {
+            Self::Diffuse(albedo) => *albedo * (f64::max(0.0, hit.normal.dot(wi)) / PI),
+            Self::Glass {
+                refraction_index,
+                roughness,
+                ..
+            } if *roughness > 0.0 => {
+                let (value, _) = rough_dielectric(hit, wi, wo, *refraction_index, *roughness);
+                Color::new(value, value, value)
+            }
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Density of sample picking wi, 0 for delta lobes.
+    pub fn pdf(&self, hit: &Hit, wi: Vec3, wo: Vec3) -> f64 {
+        match self {
+            Self::Diffuse(_) => f64::max(0.0, hit.normal.dot(wi)) / PI,
+            Self::Glass {
+                refraction_index,
+                roughness,
+                ..
+            } if *roughness > 0.0 => rough_dielectric(hit, wi, wo, *refraction_index, *roughness).1,
+            _ => 0.0,
+        }
+    }
+
+    // Share of the light surviving distance inside the material up to a hit
+    // from within, by the Beer-Lambert law.
+    pub fn transmittance(&self, hit: &Hit, distance: f64) -> Color {
+        match self {
+            Self::Glass {
+                color,
+                distance: color_distance,
+                ..
+            } if !hit.front => {
+                let exponent = distance / *color_distance;
+                Color::new(
+                    f64::powf(color.x, exponent),
+                    f64::powf(color.y, exponent),
+                    f64::powf(color.z, exponent),
+                )
+            }
+            _ => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    // The weight and the ray leaving the hit, for following a single path.
     pub fn scatter(&self, ray: &Ray, hit: &Hit) -> Option<(Color, Ray)> {
+        let sample = self.sample(hit, -ray.direction.unit())?;
+        Some((
+            sample.weight,
+            Ray::with_time(hit.point, sample.direction, ray.time),
+        ))
+    }
+
+    pub fn emitted(&self, hit: &Hit) -> Color {
         match self {
-            Self::Diffuse(albedo) => diffuse_scatter(ray, hit, albedo),
-            Self::Metalic(albedo, fuzziness) => metalic_scatter(ray, hit, albedo, fuzziness),
-            Self::Dielectric(refraction_index) => dielectric_scatter(ray, hit, refraction_index),
+            Self::Emissive(color, intensity) if hit.front => *color * *intensity,
+            _ => Color::new(0.0, 0.0, 0.0),
         }
     }
 }
 
-fn diffuse_scatter(_ray: &Ray, hit: &Hit, albedo: &Color) -> Option<(Color, Ray)> {
-    let mut scatter_direction = Vec3::random_in_hemisphere(&hit.normal);
-    if scatter_direction.nearly_zero() {
-        scatter_direction = hit.normal;
+// Cosine weighted over the hemisphere, the Lambertian reflection.
+fn diffuse_sample(hit: &Hit, albedo: &Color) -> Option<ScatterSample> {
+    let mut rng = thread_rng();
+    let (tangent, bitangent) = hit.normal.orthonormal_basis();
+    let r2 = rng.gen::<f64>();
+    let (sin_phi, cos_phi) = f64::sin_cos(2.0 * PI * rng.gen::<f64>());
+    let cosine = f64::sqrt(1.0 - r2);
+    let r = f64::sqrt(r2);
+    let direction = tangent * (r * cos_phi) + bitangent * (r * sin_phi) + hit.normal * cosine;
+
+    if cosine <= 0.0 {
+        return None;
     }
-    Some((*albedo, Ray::new(hit.point, scatter_direction)))
+    Some(ScatterSample {
+        direction,
+        weight: *albedo,
+        pdf: cosine / PI,
+        delta: false,
+    })
 }
 
-fn metalic_scatter(ray: &Ray, hit: &Hit, albedo: &Color, fuziness: &f64) -> Option<(Color, Ray)> {
+// Fuzzy metals have no density to evaluate either, they count as delta.
+fn metalic_sample(hit: &Hit, wo: Vec3, albedo: &Color, fuziness: &f64) -> Option<ScatterSample> {
     let fuzz = f64::abs(*fuziness);
-    let reflected =
-        ray.direction.unit().reflect(hit.normal) + (Vec3::random_in_unit_sphere() * fuzz);
+    let reflected = (-wo).reflect(hit.normal) + (Vec3::random_in_unit_sphere() * fuzz);
     if reflected.dot(hit.normal) > 0.0 {
-        return Some((*albedo, Ray::new(hit.point, reflected)));
+        return Some(ScatterSample {
+            direction: reflected.unit(),
+            weight: *albedo,
+            pdf: 0.0,
+            delta: true,
+        });
     }
 
     None
 }
 
-fn dielectric_scatter(ray: &Ray, hit: &Hit, refraction_index: &f64) -> Option<(Color, Ray)> {
-    let refraction_ratio = match hit.front {
-        true => 1.0 / *refraction_index,
-        false => *refraction_index,
+// Mirror reflection or refraction, picked by the Fresnel reflectance.
+fn dielectric_sample(hit: &Hit, wo: Vec3, refraction_index: f64) -> Option<ScatterSample> {
+    let eta = match hit.front {
+        true => refraction_index,
+        false => 1.0 / refraction_index,
+    };
+
+    let cos_theta = f64::min(wo.dot(hit.normal), 1.0);
+    let reflectance = fresnel(cos_theta, eta);
+    let scatter_direction = match thread_rng().gen::<f64>() < reflectance {
+        true => (-wo).reflect(hit.normal),
+        false => (-wo).refract(hit.normal, 1.0 / eta),
+    };
+
+    Some(ScatterSample {
+        direction: scatter_direction.unit(),
+        weight: Color::new(1.0, 1.0, 1.0),
+        pdf: 0.0,
+        delta: true,
+    })
+}
+
+// Unpolarized reflectance of light arriving at cos_theta to the normal on a
+// medium eta times as dense, 1 on total internal reflection.
+fn fresnel(cos_theta: f64, eta: f64) -> f64 {
+    let sin2_t = (1.0 - cos_theta * cos_theta) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+
+    let cos_t = f64::sqrt(1.0 - sin2_t);
+    let s = (cos_theta - eta * cos_t) / (cos_theta + eta * cos_t);
+    let p = (eta * cos_theta - cos_t) / (eta * cos_theta + cos_t);
+    (s * s + p * p) / 2.0
+}
+
+// GGX microfacets (Walter et al. 2007, "Microfacet Models for Refraction
+// through Rough Surfaces"), alpha is the roughness squared. The microfacet
+// normal is picked by its distribution, then reflected on or refracted
+// through by the Fresnel reflectance.
+fn rough_dielectric_sample(
+    hit: &Hit,
+    wo: Vec3,
+    refraction_index: f64,
+    roughness: f64,
+) -> Option<ScatterSample> {
+    let mut rng = thread_rng();
+    let alpha2 = ggx_alpha2(roughness);
+    let eta = match hit.front {
+        true => refraction_index,
+        false => 1.0 / refraction_index,
+    };
+
+    let r = rng.gen::<f64>();
+    let tan2 = alpha2 * r / (1.0 - r);
+    let cos_m = 1.0 / f64::sqrt(1.0 + tan2);
+    let sin_m = f64::sqrt(1.0 - cos_m * cos_m);
+    let (sin_phi, cos_phi) = f64::sin_cos(2.0 * PI * rng.gen::<f64>());
+    let (tangent, bitangent) = hit.normal.orthonormal_basis();
+    let m = tangent * (sin_m * cos_phi) + bitangent * (sin_m * sin_phi) + hit.normal * cos_m;
+
+    let cos_om = wo.dot(m);
+    if cos_om <= 0.0 {
+        return None;
+    }
+    let reflected = rng.gen::<f64>() < fresnel(cos_om, eta);
+    let direction = match reflected {
+        true => (-wo).reflect(m),
+        false => (-wo).refract(m, 1.0 / eta),
+    }
+    .unit();
+    // A microfacet tilted far from the normal can send the ray to the other
+    // side of the surface than its lobe, eval would count it in the wrong one.
+    if (direction.dot(hit.normal) > 0.0) != reflected {
+        return None;
+    }
+
+    let (value, pdf) = rough_dielectric(hit, direction, wo, refraction_index, roughness);
+    if pdf <= 0.0 {
+        return None;
+    }
+    Some(ScatterSample {
+        direction,
+        weight: Color::new(1.0, 1.0, 1.0) * (value / pdf),
+        pdf,
+        delta: false,
+    })
+}
+
+// Scattering function times the cosine of wi, and the density of sampling wi.
+// Transmission leaves out the eta squared scaling of radiance, matching the
+// smooth dielectric.
+fn rough_dielectric(
+    hit: &Hit,
+    wi: Vec3,
+    wo: Vec3,
+    refraction_index: f64,
+    roughness: f64,
+) -> (f64, f64) {
+    let alpha2 = ggx_alpha2(roughness);
+    let eta = match hit.front {
+        true => refraction_index,
+        false => 1.0 / refraction_index,
     };
 
-    let unit_direction = ray.direction.unit();
+    let normal = hit.normal;
+    let (cos_o, cos_i) = (wo.dot(normal), wi.dot(normal));
+    if cos_o <= 0.0 || cos_i == 0.0 {
+        return (0.0, 0.0);
+    }
 
-    // Snell's Law
-    let cos_theta = f64::min(-unit_direction.dot(hit.normal), 1.0);
-    let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+    let reflected = cos_i > 0.0;
+    let mut m = match reflected {
+        true => wi + wo,
+        false => -(wo + wi * eta),
+    }
+    .unit();
+    if m.dot(normal) < 0.0 {
+        m = -m;
+    }
+
+    let (cos_om, cos_im, cos_m) = (wo.dot(m), wi.dot(m), m.dot(normal));
+    if cos_om <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let d = ggx_distribution(cos_m, alpha2);
+    let g = ggx_shadowing(wi, m, normal, alpha2) * ggx_shadowing(wo, m, normal, alpha2);
+    let f = fresnel(cos_om, eta);
 
-    let scatter_direction = match refraction_ratio * sin_theta > 1.0 {
-        true => unit_direction.reflect(hit.normal),
+    match reflected {
+        true => (f * d * g / (4.0 * cos_o), f * d * cos_m / (4.0 * cos_om)),
         false => {
-            let perpendicular = (unit_direction + hit.normal * cos_theta) * refraction_ratio;
-            let parallel = hit.normal * -f64::sqrt(f64::abs(1.0 - perpendicular.magnitude2()));
-            perpendicular + parallel
+            let denominator = eta * cos_im + cos_om;
+            let jacobian = eta * eta * f64::abs(cos_im) / (denominator * denominator);
+            (
+                (1.0 - f) * d * g * cos_om * jacobian / cos_o,
+                (1.0 - f) * d * cos_m * jacobian,
+            )
         }
+    }
+}
+
+fn ggx_alpha2(roughness: f64) -> f64 {
+    let alpha = f64::max(roughness * roughness, 1e-4);
+    alpha * alpha
+}
+
+fn ggx_distribution(cos_m: f64, alpha2: f64) -> f64 {
+    if cos_m <= 0.0 {
+        return 0.0;
+    }
+    let d = cos_m * cos_m * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * d * d)
+}
+
+// Smith masking of microfacets with normal m seen from v.
+fn ggx_shadowing(v: Vec3, m: Vec3, normal: Vec3, alpha2: f64) -> f64 {
+    let cos_v = v.dot(normal);
+    if v.dot(m) * cos_v <= 0.0 {
+        return 0.0;
+    }
+    let cos2 = cos_v * cos_v;
+    let tan2 = (1.0 - cos2) / cos2;
+    2.0 / (1.0 + f64::sqrt(1.0 + alpha2 * tan2))
+}
+
+#[test]
+fn sample_eval_pdf() {
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+    let wo = Vec3::new(1.0, 1.0, 0.0).unit();
+
+    let albedo = Color::new(0.8, 0.4, 0.2);
+    let diffuse = Material::Diffuse(albedo);
+    let hit = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, true, diffuse);
+    for _ in 0..100 {
+        let sample = diffuse.sample(&hit, wo).unwrap();
+        assert!(!sample.delta);
+        assert!(sample.direction.dot(normal) > 0.0);
+        assert!(f64::abs(sample.direction.magnitude() - 1.0) < 1e-9);
+        assert!(f64::abs(diffuse.pdf(&hit, sample.direction, wo) - sample.pdf) < 1e-9);
+        let weight = diffuse.eval(&hit, sample.direction, wo) / sample.pdf;
+        assert!((weight - sample.weight).magnitude() < 1e-9);
+    }
+    assert_eq!(diffuse.pdf(&hit, -normal, wo), 0.0);
+
+    let metal = Material::Metalic(albedo, 0.0);
+    let hit = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, true, metal);
+    let sample = metal.sample(&hit, wo).unwrap();
+    assert!(sample.delta);
+    assert!((sample.direction - Vec3::new(-1.0, 1.0, 0.0).unit()).magnitude() < 1e-9);
+    assert_eq!(metal.pdf(&hit, sample.direction, wo), 0.0);
+    assert!(metal.eval(&hit, sample.direction, wo).nearly_zero());
+
+    let glass = Material::Dielectric(1.5);
+    let hit = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, true, glass);
+    assert!(glass.sample(&hit, wo).unwrap().delta);
+}
+
+#[test]
+fn glass() {
+    assert!(f64::abs(fresnel(1.0, 1.5) - 0.04) < 1e-9);
+    assert_eq!(fresnel(0.1, 1.0 / 1.5), 1.0);
+    assert!(fresnel(0.1, 1.5) > fresnel(0.9, 1.5));
+
+    // Smooth glass reflects by Fresnel, mostly at grazing angles.
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+    let glass = Material::Dielectric(1.5);
+    let hit = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, true, glass);
+    let grazing = Vec3::new(1.0, 0.05, 0.0).unit();
+    let reflected = (0..1000)
+        .filter(|_| glass.sample(&hit, grazing).unwrap().direction.y > 0.0)
+        .count();
+    assert!(reflected > 500 && reflected < 1000);
+
+    let frosted = Material::Glass {
+        refraction_index: 1.5,
+        roughness: 0.4,
+        color: Color::new(0.25, 0.5, 1.0),
+        distance: 2.0,
     };
+    for front in [true, false] {
+        let hit = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, front, frosted);
+        let wo = Vec3::new(0.3, 1.0, 0.0).unit();
+        for _ in 0..100 {
+            let sample = match frosted.sample(&hit, wo) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            assert!(!sample.delta);
+            assert!(f64::abs(frosted.pdf(&hit, sample.direction, wo) - sample.pdf) < 1e-6);
+            let weight = frosted.eval(&hit, sample.direction, wo) / sample.pdf;
+            assert!((weight - sample.weight).magnitude() < 1e-6);
+        }
+    }
+
+    // Light is tinted to the color after the distance inside.
+    let inside = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, false, frosted);
+    let transmittance = frosted.transmittance(&inside, 4.0);
+    assert!((transmittance - Color::new(0.0625, 0.25, 1.0)).nearly_zero());
+    let outside = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, true, frosted);
+    assert_eq!(
+        frosted.transmittance(&outside, 4.0),
+        Color::new(1.0, 1.0, 1.0)
+    );
+}
 
-    Some((
-        Color::new(1.0, 1.0, 1.0),
-        Ray::new(hit.point, scatter_direction),
-    ))
+#[test]
+fn rough_glass_at_grazing_angles() {
+    // Very rough glass seen from a grazing angle turns many samples to the
+    // other side of the surface than their lobe. Those are dropped, so the
+    // share of samples on each side is what the pdf integrates to there.
+    let normal = Vec3::new(0.0, 1.0, 0.0);
+    let frosted = Material::Glass {
+        refraction_index: 1.5,
+        roughness: 1.0,
+        color: Color::new(1.0, 1.0, 1.0),
+        distance: 1.0,
+    };
+    let wo = Vec3::new(1.0, 0.02, 0.0).unit();
+    let count = 200000;
+    for front in [true, false] {
+        let hit = Hit::new(Point3::new(0.0, 0.0, 0.0), normal, 1.0, front, frosted);
+        let (mut sampled_above, mut sampled_below) = (0.0, 0.0);
+        let (mut pdf_above, mut pdf_below) = (0.0, 0.0);
+        for _ in 0..count {
+            if let Some(sample) = frosted.sample(&hit, wo) {
+                assert!(f64::abs(frosted.pdf(&hit, sample.direction, wo) - sample.pdf) < 1e-6);
+                let weight = frosted.eval(&hit, sample.direction, wo) / sample.pdf;
+                assert!((weight - sample.weight).magnitude() < 1e-6);
+                match sample.direction.y > 0.0 {
+                    true => sampled_above += 1.0 / count as f64,
+                    false => sampled_below += 1.0 / count as f64,
+                }
+            }
+
+            let wi = Vec3::random_in_unit_sphere().unit();
+            let pdf = frosted.pdf(&hit, wi, wo) * 4.0 * PI / count as f64;
+            match wi.y > 0.0 {
+                true => pdf_above += pdf,
+                false => pdf_below += pdf,
+            }
+        }
+        assert!(f64::abs(sampled_above - pdf_above) < 0.02);
+        assert!(f64::abs(sampled_below - pdf_below) < 0.02);
+    }
 }
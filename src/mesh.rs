@@ -0,0 +1,382 @@
+use std::fmt;
+use std::sync::OnceLock;
+
+use super::aabb::*;
+use super::bvh::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::triangle::*;
+use super::vec3::*;
+
+// A triangle of a mesh, as indices into the mesh buffers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Face {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    pub material: usize,
+}
+
+impl Face {
+    pub fn new(positions: [usize; 3], material: usize) -> Face {
+        Face {
+            positions,
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+}
+
+// A face refering to something the mesh doesn't have.
+#[derive(Debug)]
+pub struct MeshError {
+    pub message: String,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+// Material of meshes loaded from files that don't have their own.
+pub const DEFAULT_MATERIAL: Material = Material::Diffuse(Color {
+    x: 0.8,
+    y: 0.8,
+    z: 0.8,
+});
+
+// Model file a mesh was loaded from, part is its index among the meshes of
+// the file. A material replaced all the materials of the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshSource {
+    pub file: String,
+    pub part: usize,
+    pub material: Option<Material>,
+}
+
+// Triangle mesh with shared vertex buffers, faces only store indices so
+// vertices used by several triangles are stored once. Per vertex colors, if
+// the mesh has them, replace the albedo of diffuse materials. The faces get
+// a BVH of their own, built on the first hit after they change.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    positions: Vec<Point3>,
+    colors: Vec<Color>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    materials: Vec<Material>,
+    faces: Vec<Face>,
+    bvh: OnceLock<Bvh>,
+    source: Option<MeshSource>,
+}
+
+impl Mesh {
+    pub fn new() -> Mesh {
+        Mesh::default()
+    }
+
+    pub fn from_triangles(
+        positions: Vec<Point3>,
+        indices: &[[usize; 3]],
+        material: Material,
+    ) -> Result<Mesh, MeshError> {
+        let mut mesh = Mesh {
+            positions,
+            ..Mesh::default()
+        };
+        let material = mesh.add_material(material);
+
+        for triangle in indices.iter() {
+            mesh.add_face(Face::new(*triangle, material))?;
+        }
+
+        Ok(mesh)
+    }
+
+    pub fn add_position(&mut self, position: Point3) -> usize {
+        self.positions.push(position);
+        self.positions.len() - 1
+    }
+
+    // Colors belong to the position with the same index.
+    pub fn add_color(&mut self, color: Color) -> usize {
+        self.colors.push(color);
+        self.colors.len() - 1
+    }
+
+    // Zero normals, which scans often have, are kept as they are. Hits fall
+    // back to the face normal where they make the shading normal vanish.
+    pub fn add_normal(&mut self, normal: Vec3) -> usize {
+        let magnitude = normal.magnitude();
+        self.normals.push(match magnitude > 0.0 {
+            true => normal * (1.0 / magnitude),
+            false => normal,
+        });
+        self.normals.len() - 1
+    }
+
+    pub fn add_uv(&mut self, uv: (f64, f64)) -> usize {
+        self.uvs.push(uv);
+        self.uvs.len() - 1
+    }
+
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    // Replace all materials of the mesh with a single one.
+    pub fn set_material(&mut self, material: Material) {
+        self.materials = vec![material];
+        for face in self.faces.iter_mut() {
+            face.material = 0;
+        }
+    }
+
+    // Faces are only added when all their indices are within the buffers.
+    pub fn add_face(&mut self, face: Face) -> Result<(), MeshError> {
+        let face_index = self.faces.len();
+        let check = |name: &str, indices: &[usize], count: usize| match indices
+            .iter()
+            .find(|i| **i >= count)
+        {
+            Some(i) => Err(MeshError {
+                message: format!(
+                    "face {} has {} index {} but there are {} {}s",
+                    face_index, name, i, count, name
+                ),
+            }),
+            None => Ok(()),
+        };
+
+        check("position", &face.positions, self.positions.len())?;
+        if let Some(normals) = face.normals {
+            check("normal", &normals, self.normals.len())?;
+        }
+        if let Some(uvs) = face.uvs {
+            check("uv", &uvs, self.uvs.len())?;
+        }
+        check("material", &[face.material], self.materials.len())?;
+
+        self.faces.push(face);
+        self.bvh = OnceLock::new();
+        Ok(())
+    }
+
+    pub fn positions(&self) -> &[Point3] {
+        &self.positions
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    pub fn normals(&self) -> &[Vec3] {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &[(f64, f64)] {
+        &self.uvs
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    pub fn faces(&self) -> &[Face] {
+        &self.faces
+    }
+
+    // Where the mesh was loaded from, so scenes can be saved with the file
+    // instead of the triangles.
+    pub fn source(&self) -> Option<&MeshSource> {
+        self.source.as_ref()
+    }
+
+    pub fn set_source(&mut self, source: MeshSource) {
+        self.source = Some(source);
+    }
+
+    pub fn bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| {
+            let boxes: Vec<Aabb> = self
+                .faces
+                .iter()
+                .map(|face| Aabb::from_points(face.positions.map(|i| self.positions[i])))
+                .collect();
+            Bvh::build(&boxes)
+        })
+    }
+
+    pub fn bvh_stats(&self) -> &BvhStats {
+        self.bvh().stats()
+    }
+
+    fn has_colors(&self) -> bool {
+        !self.colors.is_empty() && self.colors.len() == self.positions.len()
+    }
+
+    pub fn hit_face(&self, index: usize, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let face = &self.faces[index];
+        let [a, b, c] = face.positions.map(|i| self.positions[i]);
+        let (solution, u, v) = intersect_triangle(ray, a, b, c, minimum, maximum)?;
+        let w = 1.0 - u - v;
+
+        let geometric_normal = (b - a).cross(c - a).unit();
+        let front = ray.direction.dot(geometric_normal) < 0.0;
+
+        let interpolated = face
+            .normals
+            .map(|[na, nb, nc]| self.normals[na] * w + self.normals[nb] * u + self.normals[nc] * v);
+        let mut normal = match interpolated {
+            Some(n) if n.magnitude2().is_finite() && n.magnitude2() > 1e-12 => n.unit(),
+            _ => geometric_normal,
+        };
+
+        // Keep the shading normal on the same side as the geometry.
+        if normal.dot(geometric_normal) < 0.0 {
+            normal = -normal;
+        }
+
+        let material = match self.materials[face.material] {
+            Material::Diffuse(_) if self.has_colors() => {
+                let [ca, cb, cc] = face.positions.map(|i| self.colors[i]);
+                Material::Diffuse(ca * w + cb * u + cc * v)
+            }
+            material => material,
+        };
+
+        let mut hit = Hit::new(ray.at(solution), normal, solution, front, material);
+
+        hit.uv = match face.uvs {
+            Some([ta, tb, tc]) => {
+                let (ta, tb, tc) = (self.uvs[ta], self.uvs[tb], self.uvs[tc]);
+                (
+                    ta.0 * w + tb.0 * u + tc.0 * v,
+                    ta.1 * w + tb.1 * u + tc.1 * v,
+                )
+            }
+            None => (u, v),
+        };
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        self.bvh()
+            .hit(ray, minimum, maximum, |index, minimum, maximum| {
+                self.hit_face(index, ray, minimum, maximum)
+            })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh().bounds()
+    }
+}
+
+#[test]
+fn hit_mesh() {
+    // Unit square in the xy plane made of two triangles sharing an edge.
+    let mesh = Mesh::from_triangles(
+        vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ],
+        &[[0, 1, 2], [0, 2, 3]],
+        Material::Diffuse(Color::new(0.5, 0.5, 0.5)),
+    )
+    .unwrap();
+
+    assert_eq!(mesh.positions().len(), 4);
+    assert_eq!(mesh.faces().len(), 2);
+
+    let ray = Ray::new(Point3::new(0.75, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+
+    let ray = Ray::new(Point3::new(0.25, 0.75, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    let hit = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+
+    let ray = Ray::new(Point3::new(1.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(mesh.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
+
+#[test]
+fn reject_bad_faces() {
+    let mut mesh = Mesh::new();
+    for i in 0..3 {
+        mesh.add_position(Point3::new(i as f64, (i % 2) as f64, 0.0));
+    }
+    mesh.add_normal(Vec3::new(0.0, 0.0, 1.0));
+    let material = mesh.add_material(DEFAULT_MATERIAL);
+
+    let error = mesh.add_face(Face::new([0, 1, 3], material)).unwrap_err();
+    assert_eq!(
+        error.message,
+        "face 0 has position index 3 but there are 3 positions"
+    );
+    let mut face = Face::new([0, 1, 2], material);
+    face.normals = Some([0, 1, 2]);
+    assert!(mesh.add_face(face).is_err());
+    assert!(mesh.add_face(Face::new([0, 1, 2], material + 1)).is_err());
+    assert!(mesh.faces().is_empty());
+
+    face.normals = Some([0, 0, 0]);
+    mesh.add_face(face).unwrap();
+    assert_eq!(mesh.faces().len(), 1);
+}
+
+#[test]
+fn zero_normals() {
+    let triangle = |normals: [Vec3; 3]| {
+        let mut mesh = Mesh::new();
+        mesh.add_position(Point3::new(0.0, 0.0, 0.0));
+        mesh.add_position(Point3::new(1.0, 0.0, 0.0));
+        mesh.add_position(Point3::new(0.0, 1.0, 0.0));
+        let mut face = Face::new([0, 1, 2], mesh.add_material(DEFAULT_MATERIAL));
+        face.normals = Some(normals.map(|n| mesh.add_normal(n)));
+        mesh.add_face(face).unwrap();
+        mesh
+    };
+    let (zero, up, down) = (
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 2.0),
+        Vec3::new(-1.0, 0.0, -2.0),
+    );
+
+    let mesh = triangle([zero, zero, up]);
+    assert_eq!(mesh.normals()[0], zero);
+    assert!((mesh.normals()[2].magnitude() - 1.0).abs() < 1e-12);
+
+    // Zero normals and opposing normals cancelling out both shade with the
+    // face normal.
+    let ray = Ray::new(Point3::new(0.4, 0.2, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = triangle([zero, zero, zero])
+        .hit(&ray, 0.0, f64::INFINITY)
+        .unwrap();
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+    let hit = triangle([up, down, zero])
+        .hit(&ray, 0.0, f64::INFINITY)
+        .unwrap();
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+    let hit = triangle([up, up, zero])
+        .hit(&ray, 0.0, f64::INFINITY)
+        .unwrap();
+    assert!(hit.normal.nearly_eq(up.unit()));
+}
@@ -0,0 +1,547 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use super::material::*;
+use super::mesh::*;
+use super::vec3::*;
+
+// Wavefront OBJ/MTL import. Every object (`o`) or group (`g`) in the file
+// becomes its own mesh, polygons are triangulated as fans.
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+fn error(file: &str, line: usize, message: String) -> ObjError {
+    ObjError {
+        file: file.to_string(),
+        line,
+        message,
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjObject {
+    pub name: String,
+    pub mesh: Mesh,
+}
+
+// Material as described by a .mtl file, before it is mapped to a Material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    pub diffuse: Color,
+    pub specular: Color,
+    pub specular_exponent: f64,
+    pub refraction_index: f64,
+    pub dissolve: f64,
+    pub emission: Color,
+    pub illumination: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            specular_exponent: 0.0,
+            refraction_index: 1.0,
+            dissolve: 1.0,
+            emission: Color::new(0.0, 0.0, 0.0),
+            illumination: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Emitting materials (Ke) are lights, transparent ones dielectrics,
+    // strongly specular ones metals with the fuzziness derived from the
+    // specular exponent, anything else is diffuse.
+    pub fn to_material(&self) -> Material {
+        if !self.emission.nearly_zero() {
+            return Material::Emissive(self.emission, 1.0);
+        }
+
+        let transparent = self.dissolve < 1.0 || matches!(self.illumination, 4 | 6 | 7 | 9);
+        if transparent {
+            let refraction_index = match self.refraction_index > 1.0 {
+                true => self.refraction_index,
+                false => 1.5,
+            };
+            return Material::Dielectric(refraction_index);
+        }
+
+        let specular = luminance(self.specular);
+        if self.illumination == 3 || (specular > 0.0 && specular > luminance(self.diffuse)) {
+            let fuzziness = f64::sqrt(2.0 / (self.specular_exponent + 2.0));
+            return Material::Metalic(self.specular, fuzziness);
+        }
+
+        Material::Diffuse(self.diffuse)
+    }
+}
+
+fn luminance(c: Color) -> f64 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Vec<ObjObject>, ObjError> {
+    let path = path.as_ref();
+    let filename = path.display().to_string();
+    let file = File::open(path).map_err(|e| error(&filename, 0, e.to_string()))?;
+    parse_obj(BufReader::new(file), &filename, path.parent())
+}
+
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let path = path.as_ref();
+    let filename = path.display().to_string();
+    let file = File::open(path).map_err(|e| error(&filename, 0, e.to_string()))?;
+    parse_mtl(BufReader::new(file), &filename)
+}
+
+// Material libraries are looked up in mtl_directory, if it is None `mtllib`
+// statements are ignored and every face gets the default material.
+pub fn parse_obj<R: BufRead>(
+    reader: R,
+    filename: &str,
+    mtl_directory: Option<&Path>,
+) -> Result<Vec<ObjObject>, ObjError> {
+    let mut positions = Vec::<Point3>::new();
+    let mut normals = Vec::<Vec3>::new();
+    let mut uvs = Vec::<(f64, f64)>::new();
+    let mut library = HashMap::<String, Material>::new();
+
+    let mut objects = Vec::<ObjObject>::new();
+    let mut builder = ObjectBuilder::new(String::from("default"));
+    let mut material = DEFAULT_MATERIAL;
+
+    for (index, line) in reader.lines().enumerate() {
+        let number = index + 1;
+        let line = line.map_err(|e| error(filename, number, e.to_string()))?;
+        let mut words = statement(&line);
+
+        let keyword = match words.next() {
+            None => continue,
+            Some(keyword) => keyword,
+        };
+
+        match keyword {
+            "v" => positions.push(parse_vec3(filename, number, &mut words)?),
+            "vn" => normals.push(parse_vec3(filename, number, &mut words)?),
+            "vt" => {
+                let u = parse_f64(filename, number, words.next())?;
+                let v = match words.next() {
+                    Some(v) => parse_f64(filename, number, Some(v))?,
+                    None => 0.0,
+                };
+                uvs.push((u, v));
+            }
+            "f" => {
+                let mut corners = Vec::<Corner>::new();
+                for word in words {
+                    let corner = parse_corner(word, (positions.len(), uvs.len(), normals.len()))
+                        .map_err(|message| error(filename, number, message))?;
+                    corners.push(corner);
+                }
+
+                if corners.len() < 3 {
+                    return Err(error(
+                        filename,
+                        number,
+                        format!("face has {} vertices, at least 3 needed", corners.len()),
+                    ));
+                }
+
+                builder
+                    .add_polygon(&corners, material, &positions, &normals, &uvs)
+                    .map_err(|e| error(filename, number, e.message))?;
+            }
+            "o" | "g" => {
+                let name = words.collect::<Vec<&str>>().join(" ");
+                let next = ObjectBuilder::new(name);
+                let finished = std::mem::replace(&mut builder, next);
+                if let Some(object) = finished.build() {
+                    objects.push(object);
+                }
+            }
+            "usemtl" => {
+                let name = words.collect::<Vec<&str>>().join(" ");
+                material = library.get(&name).copied().unwrap_or(DEFAULT_MATERIAL);
+            }
+            "mtllib" => {
+                if let Some(directory) = mtl_directory {
+                    for name in words {
+                        let materials = load_mtl(directory.join(name))?;
+                        for (name, mtl) in materials {
+                            library.insert(name, mtl.to_material());
+                        }
+                    }
+                }
+            }
+            // Smoothing groups, lines, points, curves etc. are not supported.
+            _ => (),
+        }
+    }
+
+    if let Some(object) = builder.build() {
+        objects.push(object);
+    }
+
+    Ok(objects)
+}
+
+pub fn parse_mtl<R: BufRead>(
+    reader: R,
+    filename: &str,
+) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let mut materials = HashMap::<String, MtlMaterial>::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (index, line) in reader.lines().enumerate() {
+        let number = index + 1;
+        let line = line.map_err(|e| error(filename, number, e.to_string()))?;
+        let mut words = statement(&line);
+
+        let keyword = match words.next() {
+            None => continue,
+            Some(keyword) => keyword,
+        };
+
+        if keyword == "newmtl" {
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl);
+            }
+            let name = words.collect::<Vec<&str>>().join(" ");
+            current = Some((name, MtlMaterial::default()));
+            continue;
+        }
+
+        let mtl = match current.as_mut() {
+            Some((_, mtl)) => mtl,
+            None => {
+                return Err(error(
+                    filename,
+                    number,
+                    format!("`{}` before any `newmtl`", keyword),
+                ))
+            }
+        };
+
+        match keyword {
+            "Kd" => mtl.diffuse = parse_vec3(filename, number, &mut words)?,
+            "Ks" => mtl.specular = parse_vec3(filename, number, &mut words)?,
+            "Ke" => mtl.emission = parse_vec3(filename, number, &mut words)?,
+            "Ns" => mtl.specular_exponent = parse_f64(filename, number, words.next())?,
+            "Ni" => mtl.refraction_index = parse_f64(filename, number, words.next())?,
+            "d" => mtl.dissolve = parse_f64(filename, number, words.next())?,
+            "Tr" => mtl.dissolve = 1.0 - parse_f64(filename, number, words.next())?,
+            "illum" => {
+                let word = words.next().unwrap_or("");
+                mtl.illumination = word.parse::<u32>().map_err(|_| {
+                    error(
+                        filename,
+                        number,
+                        format!("expected an illumination model, found `{}`", word),
+                    )
+                })?;
+            }
+            // Texture maps and other extensions are not supported.
+            _ => (),
+        }
+    }
+
+    if let Some((name, mtl)) = current.take() {
+        materials.insert(name, mtl);
+    }
+
+    Ok(materials)
+}
+
+fn statement(line: &str) -> std::str::SplitWhitespace<'_> {
+    let line = match line.find('#') {
+        Some(index) => &line[..index],
+        None => line,
+    };
+    line.split_whitespace()
+}
+
+fn parse_f64(filename: &str, line: usize, word: Option<&str>) -> Result<f64, ObjError> {
+    match word {
+        None => Err(error(filename, line, String::from("missing number"))),
+        Some(word) => match word.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(n),
+            _ => Err(error(
+                filename,
+                line,
+                format!("expected a number, found `{}`", word),
+            )),
+        },
+    }
+}
+
+fn parse_vec3<'a, I: Iterator<Item = &'a str>>(
+    filename: &str,
+    line: usize,
+    words: &mut I,
+) -> Result<Vec3, ObjError> {
+    let x = parse_f64(filename, line, words.next())?;
+    let y = parse_f64(filename, line, words.next())?;
+    let z = parse_f64(filename, line, words.next())?;
+    Ok(Vec3::new(x, y, z))
+}
+
+// Indices of one face corner into the file wide position/uv/normal lists.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+fn parse_corner(word: &str, counts: (usize, usize, usize)) -> Result<Corner, String> {
+    let mut parts = word.split('/');
+
+    let position = resolve_index(parts.next(), counts.0, "vertex", word)?;
+    let uv = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(resolve_index(
+            Some(part),
+            counts.1,
+            "texture coordinate",
+            word,
+        )?),
+    };
+    let normal = match parts.next() {
+        None | Some("") => None,
+        Some(part) => Some(resolve_index(Some(part), counts.2, "normal", word)?),
+    };
+
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex `{}`", word));
+    }
+
+    Ok(Corner {
+        position,
+        uv,
+        normal,
+    })
+}
+
+// OBJ indices start at 1, negative indices count back from the last element.
+fn resolve_index(
+    part: Option<&str>,
+    count: usize,
+    what: &str,
+    word: &str,
+) -> Result<usize, String> {
+    let part = part.unwrap_or("");
+    let index = part
+        .parse::<i64>()
+        .map_err(|_| format!("malformed face vertex `{}`", word))?;
+
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => count as i64 + i,
+        _ => {
+            return Err(format!(
+                "{} index 0 in `{}`, indices start at 1",
+                what, word
+            ))
+        }
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!(
+            "{} index {} in `{}` is out of range, there are {}",
+            what, index, word, count
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+// Collects the faces of one object, copying only the vertices it uses out of
+// the file wide lists.
+struct ObjectBuilder {
+    name: String,
+    mesh: Mesh,
+    positions: HashMap<usize, usize>,
+    normals: HashMap<usize, usize>,
+    uvs: HashMap<usize, usize>,
+    materials: Vec<Material>,
+}
+
+impl ObjectBuilder {
+    fn new(name: String) -> ObjectBuilder {
+        ObjectBuilder {
+            name,
+            mesh: Mesh::new(),
+            positions: HashMap::new(),
+            normals: HashMap::new(),
+            uvs: HashMap::new(),
+            materials: Vec::new(),
+        }
+    }
+
+    fn add_polygon(
+        &mut self,
+        corners: &[Corner],
+        material: Material,
+        positions: &[Point3],
+        normals: &[Vec3],
+        uvs: &[(f64, f64)],
+    ) -> Result<(), MeshError> {
+        let material = match self.materials.iter().position(|m| *m == material) {
+            Some(index) => index,
+            None => {
+                self.materials.push(material);
+                self.mesh.add_material(material)
+            }
+        };
+
+        let mut local = Vec::<Corner>::with_capacity(corners.len());
+        for corner in corners.iter() {
+            let mesh = &mut self.mesh;
+            let position = *self
+                .positions
+                .entry(corner.position)
+                .or_insert_with(|| mesh.add_position(positions[corner.position]));
+            let uv = corner
+                .uv
+                .map(|i| *self.uvs.entry(i).or_insert_with(|| mesh.add_uv(uvs[i])));
+            let normal = corner.normal.map(|i| {
+                *self
+                    .normals
+                    .entry(i)
+                    .or_insert_with(|| mesh.add_normal(normals[i]))
+            });
+            local.push(Corner {
+                position,
+                uv,
+                normal,
+            });
+        }
+
+        for i in 1..local.len() - 1 {
+            let triangle = [local[0], local[i], local[i + 1]];
+            let mut face = Face::new(triangle.map(|c| c.position), material);
+            if triangle.iter().all(|c| c.normal.is_some()) {
+                face.normals = Some(triangle.map(|c| c.normal.unwrap()));
+            }
+            if triangle.iter().all(|c| c.uv.is_some()) {
+                face.uvs = Some(triangle.map(|c| c.uv.unwrap()));
+            }
+            self.mesh.add_face(face)?;
+        }
+        Ok(())
+    }
+
+    fn build(self) -> Option<ObjObject> {
+        if self.mesh.faces().is_empty() {
+            return None;
+        }
+
+        Some(ObjObject {
+            name: self.name,
+            mesh: self.mesh,
+        })
+    }
+}
+
+#[test]
+fn parse_obj_objects() {
+    let text = "# two objects
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 1
+o quad
+f 1/1/1 2/2/1 3/3/1 4/1/1
+o triangle
+f -4 -3 -1
+";
+
+    let objects = parse_obj(text.as_bytes(), "test.obj", None).unwrap();
+    assert_eq!(objects.len(), 2);
+
+    assert_eq!(objects[0].name, "quad");
+    assert_eq!(objects[0].mesh.faces().len(), 2);
+    assert_eq!(objects[0].mesh.positions().len(), 4);
+    assert_eq!(objects[0].mesh.normals().len(), 1);
+    assert_eq!(objects[0].mesh.uvs().len(), 3);
+    assert_eq!(objects[0].mesh.faces()[1].normals, Some([0, 0, 0]));
+
+    assert_eq!(objects[1].name, "triangle");
+    assert_eq!(objects[1].mesh.faces().len(), 1);
+    assert_eq!(objects[1].mesh.positions().len(), 3);
+    assert_eq!(objects[1].mesh.faces()[0].normals, None);
+    assert_eq!(objects[1].mesh.materials(), &[DEFAULT_MATERIAL]);
+}
+
+#[test]
+fn parse_obj_errors() {
+    let e = parse_obj("v 0 0 0\nv 1 0 x\n".as_bytes(), "bad.obj", None).unwrap_err();
+    assert_eq!(e.to_string(), "bad.obj:2: expected a number, found `x`");
+
+    let e = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n".as_bytes(), "bad.obj", None).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "bad.obj:3: vertex index 3 in `3` is out of range, there are 2"
+    );
+
+    let e = parse_obj("v 0 0 0\nf 1 1\n".as_bytes(), "bad.obj", None).unwrap_err();
+    assert_eq!(e.line, 2);
+}
+
+#[test]
+fn parse_mtl_materials() {
+    let text = "newmtl red
+Kd 0.8 0.1 0.1
+newmtl glass
+Ni 1.45
+d 0.2
+newmtl chrome
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+Ns 98
+newmtl lamp
+Kd 0.8 0.8 0.8
+Ke 4 3 2
+";
+
+    let materials = parse_mtl(text.as_bytes(), "test.mtl").unwrap();
+    assert_eq!(
+        materials["red"].to_material(),
+        Material::Diffuse(Color::new(0.8, 0.1, 0.1))
+    );
+    assert_eq!(materials["glass"].to_material(), Material::Dielectric(1.45));
+    assert_eq!(
+        materials["chrome"].to_material(),
+        Material::Metalic(Color::new(0.9, 0.9, 0.9), 0.1414213562373095)
+    );
+    assert_eq!(
+        materials["lamp"].to_material(),
+        Material::Emissive(Color::new(4.0, 3.0, 2.0), 1.0)
+    );
+}
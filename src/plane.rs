@@ -0,0 +1,207 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::vec3::*;
+
+// Infinite plane through point, facing towards normal. It has no bounding
+// box, the uv coordinates are distances along the plane and repeat nowhere.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    tangents: (Vec3, Vec3),
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: Material) -> Plane {
+        let normal = unit_normal(normal);
+        Plane {
+            point,
+            normal,
+            tangents: normal.orthonormal_basis(),
+            material,
+        }
+    }
+
+    pub fn point(&self) -> Point3 {
+        self.point
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let (solution, front) = intersect_plane(ray, self.point, self.normal, minimum, maximum)?;
+        let point = ray.at(solution);
+        let p = point - self.point;
+
+        let mut hit = Hit::new(point, self.normal, solution, front, self.material);
+        hit.uv = (p.dot(self.tangents.0), p.dot(self.tangents.1));
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+}
+
+// Round flat disk at center, facing towards normal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    radius: f64,
+    tangents: (Vec3, Vec3),
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: Material) -> Disk {
+        let normal = unit_normal(normal);
+        Disk {
+            center,
+            normal,
+            radius,
+            tangents: normal.orthonormal_basis(),
+            material,
+        }
+    }
+
+    pub fn center(&self) -> Point3 {
+        self.center
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let (solution, front) = intersect_plane(ray, self.center, self.normal, minimum, maximum)?;
+        let point = ray.at(solution);
+        let p = point - self.center;
+        if p.magnitude2() > self.radius * self.radius {
+            return None;
+        }
+
+        let mut hit = Hit::new(point, self.normal, solution, front, self.material);
+        // The disk fits the unit square.
+        let scale = 0.5 / self.radius;
+        hit.uv = (
+            0.5 + p.dot(self.tangents.0) * scale,
+            0.5 + p.dot(self.tangents.1) * scale,
+        );
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // The extent of a circle along each axis.
+        let n = self.normal;
+        let extent = Vec3::new(
+            f64::sqrt(f64::max(1.0 - n.x * n.x, 0.0)),
+            f64::sqrt(f64::max(1.0 - n.y * n.y, 0.0)),
+            f64::sqrt(f64::max(1.0 - n.z * n.z, 0.0)),
+        ) * self.radius;
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+// Solution and whether the front was hit, None if parallel or out of range.
+pub fn intersect_plane(
+    ray: &Ray,
+    point: Point3,
+    normal: Vec3,
+    minimum: f64,
+    maximum: f64,
+) -> Option<(f64, bool)> {
+    let denominator = normal.dot(ray.direction);
+    if f64::abs(denominator) < 1e-12 {
+        return None;
+    }
+
+    let solution = normal.dot(point - ray.origin) / denominator;
+    if solution < minimum || solution > maximum {
+        return None;
+    }
+
+    Some((solution, denominator < 0.0))
+}
+
+// Normals that are already unit vectors are kept as they are, normalizing
+// them again could change the last bits and saved scenes wouldn't load back
+// the same.
+fn unit_normal(normal: Vec3) -> Vec3 {
+    match f64::abs(normal.magnitude2() - 1.0) <= EPSILON {
+        true => normal,
+        false => normal.unit(),
+    }
+}
+
+#[test]
+fn hit_plane() {
+    let plane = Plane::new(
+        Point3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 2.0, 0.0),
+        Material::Dielectric(1.5),
+    );
+    assert!(plane.bounding_box().is_none());
+
+    // Grazing rays far away still hit at the right height.
+    let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1000.0, -1.0, 0.0));
+    let hit = plane.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert_eq!(hit.point.y, -1.0);
+    assert_eq!(hit.normal, Vec3::new(0.0, 1.0, 0.0));
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+    assert!(plane.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
+
+#[test]
+fn hit_disk() {
+    let disk = Disk::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        2.0,
+        Material::Dielectric(1.5),
+    );
+    assert_eq!(
+        disk.bounding_box(),
+        Some(Aabb::new(
+            Point3::new(-2.0, -2.0, 0.0),
+            Point3::new(2.0, 2.0, 0.0)
+        ))
+    );
+
+    let ray = Ray::new(Point3::new(1.0, 1.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    let hit = disk.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+
+    let ray = Ray::new(Point3::new(1.5, 1.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    assert!(disk.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
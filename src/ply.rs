@@ -0,0 +1,524 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use super::mesh::*;
+use super::vec3::*;
+
+// PLY (Stanford polygon file) import, ASCII and binary in either byte order.
+// Vertex positions, normals and colors and the faces are read one element at
+// a time so large scans don't need to be held in memory twice.
+
+#[derive(Debug)]
+pub struct PlyError {
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<Mesh, PlyError> {
+    let path = path.as_ref();
+    let filename = path.display().to_string();
+    let file = File::open(path).map_err(|e| PlyError {
+        file: filename.clone(),
+        message: e.to_string(),
+    })?;
+
+    parse_ply(BufReader::new(file), &filename)
+}
+
+pub fn parse_ply<R: BufRead>(mut reader: R, filename: &str) -> Result<Mesh, PlyError> {
+    let error = |message: String| PlyError {
+        file: filename.to_string(),
+        message,
+    };
+
+    let header = read_header(&mut reader).map_err(error)?;
+    let mut source = match header.format {
+        Format::Ascii => Source::Ascii(AsciiSource {
+            reader: &mut reader,
+            words: Vec::new(),
+            line: header.lines,
+        }),
+        Format::BinaryLittleEndian => Source::Binary(&mut reader, false),
+        Format::BinaryBigEndian => Source::Binary(&mut reader, true),
+    };
+
+    let mut mesh = Mesh::new();
+    let material = mesh.add_material(DEFAULT_MATERIAL);
+    let mut vertex_count = 0;
+
+    for element in header.elements.iter() {
+        match element.name.as_str() {
+            "vertex" => {
+                let layout = VertexLayout::new(element).map_err(error)?;
+                let mut values = Vec::with_capacity(element.properties.len());
+                for _ in 0..element.count {
+                    read_vertex(&mut source, element, &layout, &mut values, &mut mesh)
+                        .map_err(error)?;
+                }
+                vertex_count = element.count;
+            }
+            "face" => {
+                let list = element
+                    .properties
+                    .iter()
+                    .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")
+                    .ok_or_else(|| error(String::from("face element has no vertex_indices")))?;
+
+                let mut indices = Vec::<usize>::new();
+                for face in 0..element.count {
+                    indices.clear();
+                    for (index, property) in element.properties.iter().enumerate() {
+                        let values = source.read_property(property).map_err(error)?;
+                        if index == list {
+                            for value in values {
+                                if value < 0.0 || value as usize >= vertex_count {
+                                    return Err(error(format!(
+                                        "face {} has vertex index {} but there are {} vertices",
+                                        face, value, vertex_count
+                                    )));
+                                }
+                                indices.push(value as usize);
+                            }
+                        }
+                    }
+
+                    if indices.len() < 3 {
+                        return Err(error(format!(
+                            "face {} has {} vertices, at least 3 needed",
+                            face,
+                            indices.len()
+                        )));
+                    }
+
+                    let has_normals = !mesh.normals().is_empty();
+                    for i in 1..indices.len() - 1 {
+                        let triangle = [indices[0], indices[i], indices[i + 1]];
+                        let mut face = Face::new(triangle, material);
+                        if has_normals {
+                            face.normals = Some(triangle);
+                        }
+                        mesh.add_face(face).map_err(|e| error(e.message))?;
+                    }
+                }
+            }
+            // Other elements (edges, materials, ...) are skipped.
+            _ => {
+                for _ in 0..element.count {
+                    for property in element.properties.iter() {
+                        source.read_property(property).map_err(error)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(mesh)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Scalar> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Largest value of integer types, colors stored as integers are scaled
+    // by it into [0, 1].
+    fn color_scale(&self) -> f64 {
+        match self {
+            Scalar::I8 => 127.0,
+            Scalar::U8 => 255.0,
+            Scalar::I16 => 32767.0,
+            Scalar::U16 => 65535.0,
+            Scalar::I32 => 2147483647.0,
+            Scalar::U32 => 4294967295.0,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    scalar: Scalar,
+    // Type of the element count for list properties.
+    list: Option<Scalar>,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    lines: usize,
+}
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<Header, String> {
+    let mut format = None;
+    let mut elements = Vec::<Element>::new();
+    let mut lines = 0;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err(String::from("unexpected end of file in header"));
+        }
+        lines += 1;
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let at = |message: String| format!("line {}: {}", lines, message);
+
+        if lines == 1 {
+            if words != ["ply"] {
+                return Err(String::from("not a PLY file"));
+            }
+            continue;
+        }
+
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => (),
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(at(format!("unknown format `{}`", name))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| at(format!("invalid element count `{}`", count)))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            ["property", "list", count, item, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| at(String::from("property before any element")))?;
+                let count = Scalar::parse(count)
+                    .ok_or_else(|| at(format!("unknown property type `{}`", count)))?;
+                let scalar = Scalar::parse(item)
+                    .ok_or_else(|| at(format!("unknown property type `{}`", item)))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    scalar,
+                    list: Some(count),
+                });
+            }
+            ["property", kind, name] => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| at(String::from("property before any element")))?;
+                let scalar = Scalar::parse(kind)
+                    .ok_or_else(|| at(format!("unknown property type `{}`", kind)))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    scalar,
+                    list: None,
+                });
+            }
+            ["end_header"] => break,
+            _ => return Err(at(format!("malformed header line `{}`", line.trim()))),
+        }
+    }
+
+    match format {
+        Some(format) => Ok(Header {
+            format,
+            elements,
+            lines,
+        }),
+        None => Err(String::from("header has no format")),
+    }
+}
+
+struct AsciiSource<'a, R: BufRead> {
+    reader: &'a mut R,
+    words: Vec<String>,
+    line: usize,
+}
+
+enum Source<'a, R: BufRead> {
+    Ascii(AsciiSource<'a, R>),
+    // The flag is set for big endian data.
+    Binary(&'a mut R, bool),
+}
+
+impl<'a, R: BufRead> Source<'a, R> {
+    fn read_scalar(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Source::Ascii(source) => {
+                while source.words.is_empty() {
+                    let mut line = String::new();
+                    if source
+                        .reader
+                        .read_line(&mut line)
+                        .map_err(|e| e.to_string())?
+                        == 0
+                    {
+                        return Err(String::from("unexpected end of file"));
+                    }
+                    source.line += 1;
+                    source.words = line.split_whitespace().rev().map(String::from).collect();
+                }
+
+                let word = source.words.pop().unwrap();
+                word.parse::<f64>()
+                    .ok()
+                    .filter(|n| n.is_finite())
+                    .ok_or_else(|| {
+                        format!("line {}: expected a number, found `{}`", source.line, word)
+                    })
+            }
+            Source::Binary(reader, big_endian) => {
+                let mut bytes = [0u8; 8];
+                let bytes = &mut bytes[..scalar.size()];
+                reader
+                    .read_exact(bytes)
+                    .map_err(|_| String::from("unexpected end of file"))?;
+                if *big_endian {
+                    bytes.reverse();
+                }
+
+                Ok(match scalar {
+                    Scalar::I8 => bytes[0] as i8 as f64,
+                    Scalar::U8 => bytes[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                    Scalar::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                    Scalar::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                    Scalar::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+                })
+            }
+        }
+    }
+
+    fn read_property(&mut self, property: &Property) -> Result<Vec<f64>, String> {
+        match property.list {
+            None => Ok(vec![self.read_scalar(property.scalar)?]),
+            Some(count) => {
+                let count = self.read_scalar(count)?;
+                if count < 0.0 {
+                    return Err(format!("negative list length {}", count));
+                }
+                (0..count as usize)
+                    .map(|_| self.read_scalar(property.scalar))
+                    .collect()
+            }
+        }
+    }
+}
+
+// Where the interesting vertex properties are in the element.
+struct VertexLayout {
+    position: [usize; 3],
+    normal: Option<[usize; 3]>,
+    color: Option<[usize; 3]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Result<VertexLayout, String> {
+        let find = |names: &[&str]| -> Option<[usize; 3]> {
+            let mut out = [0; 3];
+            for (i, name) in names.iter().enumerate() {
+                out[i] = element.properties.iter().position(|p| p.name == *name)?;
+            }
+            Some(out)
+        };
+
+        if let Some(p) = element.properties.iter().find(|p| p.list.is_some()) {
+            return Err(format!("vertex property `{}` is a list", p.name));
+        }
+
+        Ok(VertexLayout {
+            position: find(&["x", "y", "z"])
+                .ok_or_else(|| String::from("vertex element has no x, y, z properties"))?,
+            normal: find(&["nx", "ny", "nz"]),
+            color: find(&["red", "green", "blue"]).or_else(|| find(&["r", "g", "b"])),
+        })
+    }
+}
+
+// values is scratch space, reused between vertices.
+fn read_vertex<R: BufRead>(
+    source: &mut Source<R>,
+    element: &Element,
+    layout: &VertexLayout,
+    values: &mut Vec<f64>,
+    mesh: &mut Mesh,
+) -> Result<(), String> {
+    values.clear();
+    for property in element.properties.iter() {
+        values.push(source.read_scalar(property.scalar)?);
+    }
+
+    let vector = |[x, y, z]: [usize; 3]| Vec3::new(values[x], values[y], values[z]);
+
+    mesh.add_position(vector(layout.position));
+    if let Some(normal) = layout.normal {
+        mesh.add_normal(vector(normal));
+    }
+    if let Some(color) = layout.color {
+        let scale = 1.0 / element.properties[color[0]].scalar.color_scale();
+        mesh.add_color(vector(color) * scale);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+use super::{hittable::*, material::*, ray::*};
+
+#[test]
+fn parse_ascii_ply() {
+    let text = "ply
+format ascii 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+
+    let mesh = parse_ply(text.as_bytes(), "quad.ply").unwrap();
+    assert_eq!(mesh.positions().len(), 4);
+    assert_eq!(mesh.faces().len(), 2);
+    assert_eq!(mesh.colors()[1], Color::new(0.0, 1.0, 0.0));
+    assert!(mesh.normals().is_empty());
+
+    // Vertex colors replace the albedo of the default material.
+    let ray = Ray::new(Point3::new(1.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = mesh.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hit.material, Material::Diffuse(Color::new(0.0, 1.0, 0.0)));
+}
+
+#[test]
+fn parse_binary_ply() {
+    for big_endian in [false, true] {
+        let format = match big_endian {
+            true => "binary_big_endian",
+            false => "binary_little_endian",
+        };
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\nelement vertex 3\nproperty double x\nproperty double y\n\
+             property double z\nproperty float nx\nproperty float ny\nproperty float nz\n\
+             element face 1\nproperty list uchar ushort vertex_index\nend_header\n",
+            format
+        )
+        .into_bytes();
+
+        let vertices = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        for v in vertices.iter() {
+            for c in v.iter() {
+                bytes.extend(match big_endian {
+                    true => f64::to_be_bytes(*c),
+                    false => f64::to_le_bytes(*c),
+                });
+            }
+            for n in [0.0f32, 0.0, 1.0] {
+                bytes.extend(match big_endian {
+                    true => f32::to_be_bytes(n),
+                    false => f32::to_le_bytes(n),
+                });
+            }
+        }
+        bytes.push(3);
+        for i in [0u16, 1, 2] {
+            bytes.extend(match big_endian {
+                true => u16::to_be_bytes(i),
+                false => u16::to_le_bytes(i),
+            });
+        }
+
+        let mesh = parse_ply(bytes.as_slice(), "triangle.ply").unwrap();
+        assert_eq!(mesh.positions()[1], Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.normals()[2], Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.faces()[0].normals, Some([0, 1, 2]));
+    }
+}
+
+#[test]
+fn parse_ply_errors() {
+    let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                property float y\nproperty float z\nelement face 1\n\
+                property list uchar int vertex_indices\nend_header\n0 0 0\n3 0 1 2\n";
+    let e = parse_ply(text.as_bytes(), "bad.ply").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "bad.ply: face 0 has vertex index 1 but there are 1 vertices"
+    );
+
+    let e = parse_ply("ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n0 x 0\n".as_bytes(), "bad.ply").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "bad.ply: line 8: expected a number, found `x`"
+    );
+}
@@ -0,0 +1,171 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::vec3::*;
+
+// Parallelogram with one corner at corner and sides u and v. The front faces
+// towards u x v, the uv coordinates run from 0 to 1 along u and v.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quad {
+    corner: Point3,
+    u: Vec3,
+    v: Vec3,
+    material: Material,
+    normal: Vec3,
+    // u x v / |u x v|^2, projects hit points onto the sides.
+    w: Vec3,
+}
+
+impl Quad {
+    pub fn new(corner: Point3, u: Vec3, v: Vec3, material: Material) -> Quad {
+        let n = u.cross(v);
+        Quad {
+            corner,
+            u,
+            v,
+            material,
+            normal: n.unit(),
+            w: n / n.dot(n),
+        }
+    }
+
+    // Axis aligned rectangles at k on the remaining axis, facing towards the
+    // positive remaining axis.
+    pub fn xy(x: (f64, f64), y: (f64, f64), k: f64, material: Material) -> Quad {
+        Quad::new(
+            Point3::new(x.0, y.0, k),
+            Vec3::new(x.1 - x.0, 0.0, 0.0),
+            Vec3::new(0.0, y.1 - y.0, 0.0),
+            material,
+        )
+    }
+
+    pub fn xz(x: (f64, f64), z: (f64, f64), k: f64, material: Material) -> Quad {
+        Quad::new(
+            Point3::new(x.0, k, z.0),
+            Vec3::new(0.0, 0.0, z.1 - z.0),
+            Vec3::new(x.1 - x.0, 0.0, 0.0),
+            material,
+        )
+    }
+
+    pub fn yz(y: (f64, f64), z: (f64, f64), k: f64, material: Material) -> Quad {
+        Quad::new(
+            Point3::new(k, y.0, z.0),
+            Vec3::new(0.0, y.1 - y.0, 0.0),
+            Vec3::new(0.0, 0.0, z.1 - z.0),
+            material,
+        )
+    }
+
+    pub fn corner(&self) -> Point3 {
+        self.corner
+    }
+
+    pub fn sides(&self) -> (Vec3, Vec3) {
+        (self.u, self.v)
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.normal
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let denominator = self.normal.dot(ray.direction);
+        // Parallel rays miss.
+        if f64::abs(denominator) < 1e-12 {
+            return None;
+        }
+
+        let solution = self.normal.dot(self.corner - ray.origin) / denominator;
+        if solution < minimum || solution > maximum {
+            return None;
+        }
+
+        let point = ray.at(solution);
+        let p = point - self.corner;
+        let alpha = self.w.dot(p.cross(self.v));
+        let beta = self.w.dot(self.u.cross(p));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut hit = Hit::new(
+            point,
+            self.normal,
+            solution,
+            denominator < 0.0,
+            self.material,
+        );
+        hit.uv = (alpha, beta);
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let c = self.corner;
+        Some(Aabb::from_points([
+            c,
+            c + self.u,
+            c + self.v,
+            c + self.u + self.v,
+        ]))
+    }
+}
+
+#[test]
+fn hit_quad() {
+    let material = Material::Diffuse(Color::new(0.5, 0.5, 0.5));
+    let quad = Quad::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        material,
+    );
+
+    let ray = Ray::new(Point3::new(1.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = quad.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert_eq!(hit.solution, 1.0);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+    assert_eq!(hit.uv, (0.5, 0.5));
+
+    // Outside of the slanted side.
+    let ray = Ray::new(Point3::new(0.2, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(quad.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+    let ray = Ray::new(Point3::new(1.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    let hit = quad.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+
+    for (quad, normal) in [
+        (
+            Quad::xy((0.0, 1.0), (0.0, 1.0), 0.0, material),
+            Vec3::new(0.0, 0.0, 1.0),
+        ),
+        (
+            Quad::xz((0.0, 1.0), (0.0, 1.0), 0.0, material),
+            Vec3::new(0.0, 1.0, 0.0),
+        ),
+        (
+            Quad::yz((0.0, 1.0), (0.0, 1.0), 0.0, material),
+            Vec3::new(1.0, 0.0, 0.0),
+        ),
+    ] {
+        assert_eq!(quad.normal(), normal);
+        let aabb = quad.bounding_box().unwrap();
+        assert_eq!(aabb.extent().dot(normal), 0.0);
+    }
+}
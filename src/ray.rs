@@ -4,11 +4,21 @@ use super::vec3::*;
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
+    // When the ray was cast within the camera shutter, for moving objects.
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+        Ray::with_time(origin, direction, 0.0)
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
     #[inline]
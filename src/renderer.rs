@@ -1,4 +1,6 @@
-use super::camera::Camera;
+use super::camera::CameraModel;
+use super::hittable::Hit;
+use super::light::power_heuristic;
 use super::ray::Ray;
 use super::vec3::*;
 use super::world::World;
@@ -24,11 +26,20 @@ fn clamp(lo: f64, hi: f64, value: f64) -> f64 {
     f64::max(lo, f64::min(hi, value))
 }
 
+// What rays that hit nothing see.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Background {
+    // White to blue gradient from the horizon up.
+    Sky,
+    Color(Color),
+}
+
 pub struct Renderer {
     width: usize,
     height: usize,
     samples_per_pixel: u32,
     sampling_factor: f64,
+    background: Background,
 }
 
 impl Renderer {
@@ -40,10 +51,31 @@ impl Renderer {
             height,
             samples_per_pixel,
             sampling_factor,
+            background: Background::Sky,
         }
     }
 
-    pub fn draw_scene(&self, camera: &Camera, world: &World) -> Vec<Pixel> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    pub fn background(&self) -> Background {
+        self.background
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    pub fn draw_scene(&self, camera: &dyn CameraModel, world: &World) -> Vec<Pixel> {
         let mut pixels = vec![Pixel { r: 0, g: 0, b: 0 }; self.width * self.height];
         let scanlines: Vec<(usize, &mut [Pixel])> =
             pixels.chunks_mut(self.width).enumerate().collect();
@@ -55,23 +87,30 @@ impl Renderer {
         pixels
     }
 
-    fn draw_scanline(&self, camera: &Camera, world: &World, y: usize, scanline: &mut [Pixel]) {
+    fn draw_scanline(
+        &self,
+        camera: &dyn CameraModel,
+        world: &World,
+        y: usize,
+        scanline: &mut [Pixel],
+    ) {
         let mut rng = thread_rng();
 
-        for x in 0..self.width {
-            let mut color = Color::new(1.0, 1.0, 1.0);
+        for (x, pixel) in scanline.iter_mut().enumerate() {
+            let mut color = Color::new(0.0, 0.0, 0.0);
 
             for _ in 0..self.samples_per_pixel {
                 let u = ((x as f64 + rng.gen::<f64>()) / self.width as f64) - 0.5;
                 let v = 0.5 - ((y as f64 + rng.gen::<f64>()) / self.height as f64);
 
-                let ray = camera.cast_ray(u, v);
-                color += self.color_ray(&world, &ray, 64);
+                if let Some(ray) = camera.cast_ray(u, v) {
+                    color += self.color_ray(world, &ray, 64, None);
+                }
             }
 
             let c = color * self.sampling_factor;
 
-            scanline[x] = Pixel {
+            *pixel = Pixel {
                 r: (255.0 * clamp(0.0, 1.0, c.x)) as u8,
                 g: (255.0 * clamp(0.0, 1.0, c.y)) as u8,
                 b: (255.0 * clamp(0.0, 1.0, c.z)) as u8,
@@ -79,24 +118,80 @@ impl Renderer {
         }
     }
 
-    fn color_ray(&self, world: &World, ray: &Ray, bounce: i32) -> Color {
+    // Light is gathered both by sampling the lights at every bounce and by
+    // scattered rays hitting them, weighted by multiple importance sampling.
+    // scatter_pdf is the density the ray was scattered with, None when light
+    // sampling couldn't have found its direction.
+    fn color_ray(&self, world: &World, ray: &Ray, bounce: i32, scatter_pdf: Option<f64>) -> Color {
         if bounce <= 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
 
-        match world.test_camera_ray(ray) {
+        match world.hit_object(ray) {
             None => (),
-            Some(hit) => match hit.material.scatter(ray, &hit) {
-                None => return Color::new(0.0, 0.0, 0.0),
-                Some((attenuation, scattered_ray)) => {
-                    return self.color_ray(world, &scattered_ray, bounce - 1) * attenuation
+            Some((object, hit)) => {
+                let mut emitted = hit.material.emitted(&hit);
+                if let Some(scatter_pdf) = scatter_pdf {
+                    let light_pdf = world.light_pdf(ray.origin, ray.direction, object);
+                    emitted *= power_heuristic(scatter_pdf, light_pdf);
                 }
-            },
+
+                // Hits from inside a medium had the ray absorbed on its way.
+                let distance = hit.solution * ray.direction.magnitude();
+                let transmittance = hit.material.transmittance(&hit, distance);
+
+                let wo = -ray.direction.unit();
+                let color = match hit.material.sample(&hit, wo) {
+                    None => emitted,
+                    Some(sample) => {
+                        let scattered_ray = Ray::with_time(hit.point, sample.direction, ray.time);
+                        let next = (!sample.delta && sample.pdf > 0.0).then_some(sample.pdf);
+                        emitted
+                            + self.direct_light(world, ray, &hit)
+                            + self.color_ray(world, &scattered_ray, bounce - 1, next)
+                                * sample.weight
+                    }
+                };
+                return color * transmittance;
+            }
+        }
+
+        match self.background {
+            Background::Sky => {
+                let t = 0.5 * (ray.direction.y + 1.0);
+                let color_1 = Vec3::new(1.0, 1.0, 1.0) * (1.0 - t);
+                let color_2 = Vec3::new(0.5, 0.7, 1.0) * t;
+                color_1 + color_2
+            }
+            Background::Color(color) => color,
+        }
+    }
+
+    // Light arriving straight from one of the lights, unless something is in
+    // the way.
+    fn direct_light(&self, world: &World, ray: &Ray, hit: &Hit) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let sample = match world.sample_light(hit.point) {
+            Some(sample) => sample,
+            None => return black,
+        };
+        let wo = -ray.direction.unit();
+        let reflected = hit.material.eval(hit, sample.direction, wo);
+        if reflected.nearly_zero() {
+            return black;
+        }
+
+        let shadow_ray = Ray::with_time(hit.point, sample.direction, ray.time);
+        if let Some(blocker) = world.test_camera_ray(&shadow_ray) {
+            if blocker.solution < sample.distance * (1.0 - 1e-6) {
+                return black;
+            }
         }
 
-        let t = 0.5 * (ray.direction.y + 1.0);
-        let color_1 = Vec3::new(1.0, 1.0, 1.0) * (1.0 - t);
-        let color_2 = Vec3::new(0.5, 0.7, 1.0) * t;
-        color_1 + color_2
+        let weight = match sample.hittable {
+            true => power_heuristic(sample.pdf, hit.material.pdf(hit, sample.direction, wo)),
+            false => 1.0,
+        };
+        sample.emitted * reflected * (weight / sample.pdf)
     }
 }
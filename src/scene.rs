@@ -0,0 +1,2162 @@
+use std::any::Any;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::camera::*;
+use super::cone::*;
+use super::csg::*;
+use super::cuboid::*;
+use super::cylinder::*;
+use super::gltf::*;
+use super::heightfield::*;
+use super::hittable::*;
+use super::instance::*;
+use super::light::*;
+use super::material::*;
+use super::mesh::*;
+use super::obj::*;
+use super::plane::*;
+use super::ply::*;
+use super::quad::*;
+#[cfg(test)]
+use super::ray::*;
+use super::renderer::*;
+use super::sphere::*;
+use super::stl::*;
+use super::transform::*;
+use super::validate::*;
+use super::vec3::*;
+use super::world::*;
+
+// Image settings for glTF scenes, which have none of their own.
+pub const GLTF_WIDTH: usize = 800;
+pub const GLTF_SAMPLES_PER_PIXEL: u32 = 100;
+
+// Scene file schema, see data/test.json for an example.
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SceneFile {
+    pub image: ImageDesc,
+    pub materials: Vec<MaterialDesc>,
+    pub camera: CameraDesc,
+    // Color of everything rays don't hit, the sky when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<ColorDesc>,
+    pub world: Vec<HittableDesc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lights: Vec<LightDesc>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ImageDesc {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u32,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct VectorDesc {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct ColorDesc {
+    pub r: f64,
+    pub g: f64,
+    pub b: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "material", rename_all = "lowercase")]
+pub enum MaterialDesc {
+    Diffused {
+        color: ColorDesc,
+    },
+    Metalic {
+        color: ColorDesc,
+        fuzziness: f64,
+    },
+    // Clear and smooth unless roughness or absorption are given.
+    Dielectric {
+        refraction_index: f64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        roughness: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        absorption: Option<AbsorptionDesc>,
+    },
+    // Light source, shining from the front of surfaces.
+    Emissive {
+        color: ColorDesc,
+        intensity: f64,
+    },
+}
+
+// Light inside a dielectric is tinted to color after travelling distance.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct AbsorptionDesc {
+    pub color: ColorDesc,
+    pub distance: f64,
+}
+
+// Lights that aren't objects. Spot lights shine into a cone of cone_angle
+// degrees with the outer softness share fading out, the sun travels along
+// direction and casts soft shadows with an angular diameter in degrees.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "light", rename_all = "lowercase")]
+pub enum LightDesc {
+    Point {
+        position: VectorDesc,
+        color: ColorDesc,
+        intensity: f64,
+    },
+    Spot {
+        position: VectorDesc,
+        direction: VectorDesc,
+        color: ColorDesc,
+        intensity: f64,
+        cone_angle: f64,
+        #[serde(default)]
+        softness: f64,
+    },
+    Directional {
+        direction: VectorDesc,
+        color: ColorDesc,
+        intensity: f64,
+        #[serde(default)]
+        angular_diameter: f64,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CameraDesc {
+    pub position: VectorDesc,
+    pub look_at: VectorDesc,
+    #[serde(default = "default_world_up")]
+    pub world_up: VectorDesc,
+    // Perspective when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub projection: Option<ProjectionDesc>,
+    // One of focal_length, the fields of view in degrees or sensor sets how
+    // much of the scene is in view. Fisheyes take one of the fields of view,
+    // orthographic cameras view_height and panoramas none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focal_length: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vertical_fov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub horizontal_fov: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensor: Option<SensorDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_height: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping: Option<FisheyeMappingDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shutter: Option<ShutterDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lens: Option<LensDesc>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectionDesc {
+    Perspective,
+    Orthographic,
+    Fisheye,
+    Equirectangular,
+}
+
+// Equidistant when left out.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FisheyeMappingDesc {
+    Equidistant,
+    Equisolid,
+}
+
+// Lens focal length and sensor width in millimeters.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct SensorDesc {
+    pub width: f64,
+    pub focal_length: f64,
+}
+
+// Thin lens with an aperture radius. The focus distance defaults to the
+// distance to look_at, blades make a polygonal aperture.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct LensDesc {
+    pub aperture: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blades: Option<u32>,
+}
+
+// Moving objects move from time 0 to time 1.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct ShutterDesc {
+    pub open: f64,
+    pub close: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "hittable", rename_all = "lowercase")]
+pub enum HittableDesc {
+    Sphere {
+        position: VectorDesc,
+        radius: f64,
+        // Where a moving sphere is at time 1.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        end_position: Option<VectorDesc>,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    Mesh {
+        file: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        material: Option<usize>,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    // Parallelogram with sides u and v starting at corner.
+    Quad {
+        corner: VectorDesc,
+        u: VectorDesc,
+        v: VectorDesc,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    #[serde(rename = "box")]
+    Cuboid {
+        minimum: VectorDesc,
+        maximum: VectorDesc,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    Plane {
+        position: VectorDesc,
+        normal: VectorDesc,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    Disk {
+        position: VectorDesc,
+        normal: VectorDesc,
+        radius: f64,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    // Cylinders and cones stand on position along +y.
+    Cylinder {
+        position: VectorDesc,
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    Cone {
+        position: VectorDesc,
+        radius: f64,
+        height: f64,
+        #[serde(default = "default_capped")]
+        capped: bool,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    // Terrain over the xz plane from position, size.y scales the heights.
+    // The file is a grayscale image, or raw little endian floats when columns
+    // is given.
+    Heightfield {
+        file: String,
+        position: VectorDesc,
+        size: VectorDesc,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        columns: Option<usize>,
+        material: usize,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+    // Operands must be closed objects, meshes are not supported as operands.
+    Csg {
+        operation: CsgOperationDesc,
+        left: Box<HittableDesc>,
+        right: Box<HittableDesc>,
+        #[serde(flatten)]
+        transform: TransformDesc,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CsgOperationDesc {
+    Union,
+    Intersection,
+    Difference,
+}
+
+fn default_capped() -> bool {
+    true
+}
+
+// Applied to an object in the order scale, rotate, translate. Rotations are
+// in degrees around x, then y, then z. An object with motion moves to the
+// transform in motion at time 1, what motion leaves out stays as it is.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TransformDesc {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translate: Option<VectorDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<VectorDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<VectorDesc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motion: Option<Box<TransformDesc>>,
+}
+
+impl TransformDesc {
+    // None if the object isn't transformed.
+    pub fn to_transform(&self) -> Option<Transform> {
+        if self.translate.is_none() && self.rotate.is_none() && self.scale.is_none() {
+            return None;
+        }
+
+        let mut transform = Transform::identity();
+        if let Some(scale) = self.scale {
+            transform = transform.then(&Transform::scaling(scale.into()));
+        }
+        if let Some(rotate) = self.rotate {
+            for (axis, degrees) in [
+                (Vec3::new(1.0, 0.0, 0.0), rotate.x),
+                (Vec3::new(0.0, 1.0, 0.0), rotate.y),
+                (Vec3::new(0.0, 0.0, 1.0), rotate.z),
+            ] {
+                transform = transform.then(&Transform::rotation(axis, degrees.to_radians()));
+            }
+        }
+        if let Some(translate) = self.translate {
+            transform = transform.then(&Transform::translation(translate.into()));
+        }
+        Some(transform)
+    }
+
+    // None if the object doesn't move.
+    pub fn to_animated_transform(&self) -> Option<AnimatedTransform> {
+        let motion = self.motion.as_ref()?;
+        let end = TransformDesc {
+            translate: motion.translate.or(self.translate),
+            rotate: motion.rotate.or(self.rotate),
+            scale: motion.scale.or(self.scale),
+            motion: None,
+        };
+        Some(AnimatedTransform::new(self.pose(), end.pose(), 0.0, 1.0))
+    }
+
+    // The transform as scale, rotation and translation, None if it can't be
+    // split up that way, like a scale after a rotation that shears.
+    pub fn from_transform(transform: &Transform) -> Option<TransformDesc> {
+        let m = transform.matrix().m;
+        if m[3] != [0.0, 0.0, 0.0, 1.0] {
+            return None;
+        }
+
+        // The columns are the rotated axes times their scale, a mirroring
+        // flips x.
+        let column = |j: usize| Vec3::new(m[0][j], m[1][j], m[2][j]);
+        let mut scale = Vec3::new(
+            column(0).magnitude(),
+            column(1).magnitude(),
+            column(2).magnitude(),
+        );
+        if column(0).dot(column(1).cross(column(2))) < 0.0 {
+            scale.x = -scale.x;
+        }
+        if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+            return None;
+        }
+        let rotation = [0, 1, 2].map(|i| [m[i][0] / scale.x, m[i][1] / scale.y, m[i][2] / scale.z]);
+
+        let desc = TransformDesc::from_parts(
+            Vec3::new(m[0][3], m[1][3], m[2][3]),
+            euler_degrees(rotation),
+            Vec3::new(tidy(scale.x), tidy(scale.y), tidy(scale.z)),
+        );
+        let rebuilt = desc.to_transform().unwrap_or_default();
+        let size = m.iter().flatten().fold(1.0, |a, b| f64::max(a, b.abs()));
+        let close = (0..4)
+            .all(|i| (0..4).all(|j| f64::abs(rebuilt.matrix().m[i][j] - m[i][j]) <= 1e-9 * size));
+        close.then_some(desc)
+    }
+
+    // None unless the motion goes from time 0 to 1 like in scene files.
+    pub fn from_animated(transform: &AnimatedTransform) -> Option<TransformDesc> {
+        if transform.times() != (0.0, 1.0) {
+            return None;
+        }
+
+        let part = |pose: &Pose| {
+            let m = Transform::quaternion(pose.rotation).matrix().m;
+            let rotation = [0, 1, 2].map(|i| [m[i][0], m[i][1], m[i][2]]);
+            TransformDesc::from_parts(pose.translation, euler_degrees(rotation), pose.scale)
+        };
+        let mut desc = part(transform.start());
+        let end = part(transform.end());
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+        let one = Vec3::new(1.0, 1.0, 1.0);
+        let moved = |start: Option<VectorDesc>, end: Option<VectorDesc>, rest: Vec3| {
+            let start = start.map_or(rest, Vec3::from);
+            let end = end.map_or(rest, Vec3::from);
+            (start != end).then_some(end.into())
+        };
+        desc.motion = Some(Box::new(TransformDesc {
+            translate: moved(desc.translate, end.translate, zero),
+            rotate: moved(desc.rotate, end.rotate, zero),
+            scale: moved(desc.scale, end.scale, one),
+            motion: None,
+        }));
+        Some(desc)
+    }
+
+    // Leaves out what doesn't change anything.
+    fn from_parts(translate: Vec3, rotate: Vec3, scale: Vec3) -> TransformDesc {
+        let zero = Vec3::new(0.0, 0.0, 0.0);
+        TransformDesc {
+            translate: (translate != zero).then_some(translate.into()),
+            rotate: (rotate != zero).then_some(rotate.into()),
+            scale: (scale != Vec3::new(1.0, 1.0, 1.0)).then_some(scale.into()),
+            motion: None,
+        }
+    }
+
+    fn pose(&self) -> Pose {
+        let zero = VectorDesc {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let rotate = self.rotate.unwrap_or(zero);
+        let turn = |axis: [f64; 3], degrees: f64| {
+            let (sin, cos) = f64::sin_cos(degrees.to_radians() / 2.0);
+            [axis[0] * sin, axis[1] * sin, axis[2] * sin, cos]
+        };
+        let rotation = quaternion_product(
+            turn([0.0, 0.0, 1.0], rotate.z),
+            quaternion_product(
+                turn([0.0, 1.0, 0.0], rotate.y),
+                turn([1.0, 0.0, 0.0], rotate.x),
+            ),
+        );
+
+        Pose {
+            scale: self.scale.map_or(Vec3::new(1.0, 1.0, 1.0), Vec3::from),
+            rotation,
+            translation: self.translate.unwrap_or(zero).into(),
+        }
+    }
+}
+
+// Degrees around x, then y, then z of a rotation matrix.
+fn euler_degrees(r: [[f64; 3]; 3]) -> Vec3 {
+    let y = f64::asin(f64::clamp(-r[2][0], -1.0, 1.0));
+    let (x, z) = match f64::abs(r[2][0]) < 1.0 - 1e-12 {
+        true => (f64::atan2(r[2][1], r[2][2]), f64::atan2(r[1][0], r[0][0])),
+        // Straight up or down x and z turn around the same axis.
+        false => (0.0, f64::atan2(-r[0][1], r[1][1])),
+    };
+    let degrees = |a: f64| tidy(a.to_degrees());
+    Vec3::new(degrees(x), degrees(y), degrees(z))
+}
+
+// Snaps values that only missed a round number by rounding errors.
+fn tidy(value: f64) -> f64 {
+    let rounded = (value * 1e6).round() / 1e6;
+    match f64::abs(rounded - value) <= 1e-9 * f64::max(1.0, value.abs()) {
+        true => rounded,
+        false => value,
+    }
+}
+
+fn default_world_up() -> VectorDesc {
+    VectorDesc {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    }
+}
+
+impl From<VectorDesc> for Vec3 {
+    fn from(v: VectorDesc) -> Vec3 {
+        Vec3::new(v.x, v.y, v.z)
+    }
+}
+
+impl From<ColorDesc> for Color {
+    fn from(c: ColorDesc) -> Color {
+        Color::new(c.r, c.g, c.b)
+    }
+}
+
+impl From<Vec3> for VectorDesc {
+    fn from(v: Vec3) -> VectorDesc {
+        VectorDesc {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+impl From<Color> for ColorDesc {
+    fn from(c: Color) -> ColorDesc {
+        ColorDesc {
+            r: c.x,
+            g: c.y,
+            b: c.z,
+        }
+    }
+}
+
+impl From<FisheyeMappingDesc> for FisheyeMapping {
+    fn from(m: FisheyeMappingDesc) -> FisheyeMapping {
+        match m {
+            FisheyeMappingDesc::Equidistant => FisheyeMapping::Equidistant,
+            FisheyeMappingDesc::Equisolid => FisheyeMapping::Equisolid,
+        }
+    }
+}
+
+impl From<FisheyeMapping> for FisheyeMappingDesc {
+    fn from(m: FisheyeMapping) -> FisheyeMappingDesc {
+        match m {
+            FisheyeMapping::Equidistant => FisheyeMappingDesc::Equidistant,
+            FisheyeMapping::Equisolid => FisheyeMappingDesc::Equisolid,
+        }
+    }
+}
+
+impl From<CsgOperationDesc> for CsgOperation {
+    fn from(o: CsgOperationDesc) -> CsgOperation {
+        match o {
+            CsgOperationDesc::Union => CsgOperation::Union,
+            CsgOperationDesc::Intersection => CsgOperation::Intersection,
+            CsgOperationDesc::Difference => CsgOperation::Difference,
+        }
+    }
+}
+
+impl From<CsgOperation> for CsgOperationDesc {
+    fn from(o: CsgOperation) -> CsgOperationDesc {
+        match o {
+            CsgOperation::Union => CsgOperationDesc::Union,
+            CsgOperation::Intersection => CsgOperationDesc::Intersection,
+            CsgOperation::Difference => CsgOperationDesc::Difference,
+        }
+    }
+}
+
+impl From<&Material> for MaterialDesc {
+    fn from(m: &Material) -> MaterialDesc {
+        match *m {
+            Material::Diffuse(albedo) => MaterialDesc::Diffused {
+                color: albedo.into(),
+            },
+            Material::Metalic(albedo, fuzziness) => MaterialDesc::Metalic {
+                color: albedo.into(),
+                fuzziness,
+            },
+            Material::Dielectric(refraction_index) => MaterialDesc::Dielectric {
+                refraction_index,
+                roughness: None,
+                absorption: None,
+            },
+            Material::Glass {
+                refraction_index,
+                roughness,
+                color,
+                distance,
+            } => MaterialDesc::Dielectric {
+                refraction_index,
+                roughness: Some(roughness),
+                absorption: Some(AbsorptionDesc {
+                    color: color.into(),
+                    distance,
+                }),
+            },
+            Material::Emissive(color, intensity) => MaterialDesc::Emissive {
+                color: color.into(),
+                intensity,
+            },
+        }
+    }
+}
+
+impl From<&MaterialDesc> for Material {
+    fn from(m: &MaterialDesc) -> Material {
+        match *m {
+            MaterialDesc::Diffused { color } => Material::Diffuse(color.into()),
+            MaterialDesc::Metalic { color, fuzziness } => {
+                Material::Metalic(color.into(), fuzziness)
+            }
+            MaterialDesc::Dielectric {
+                refraction_index,
+                roughness: None,
+                absorption: None,
+            } => Material::Dielectric(refraction_index),
+            MaterialDesc::Dielectric {
+                refraction_index,
+                roughness,
+                absorption,
+            } => Material::Glass {
+                refraction_index,
+                roughness: roughness.unwrap_or(0.0),
+                color: absorption.map_or(Color::new(1.0, 1.0, 1.0), |a| a.color.into()),
+                distance: absorption.map_or(1.0, |a| a.distance),
+            },
+            MaterialDesc::Emissive { color, intensity } => {
+                Material::Emissive(color.into(), intensity)
+            }
+        }
+    }
+}
+
+impl SceneFile {
+    // Objects the scene format can't describe, like meshes made in code or
+    // transforms that shear, fail the export with a list of them. Files the
+    // objects came from are written relative to directory, where the scene
+    // file goes.
+    pub fn new(
+        camera: &dyn CameraModel,
+        world: &World,
+        renderer: &Renderer,
+        directory: &Path,
+    ) -> Result<SceneFile, SceneError> {
+        let mut materials = Vec::<Material>::new();
+        let mut world_desc = Vec::new();
+        let mut unsaved = Vec::new();
+
+        for (index, (_, object)) in world.iter().enumerate() {
+            match hittable_desc(object as &dyn Any, &mut materials, directory) {
+                Ok(Some(desc)) => world_desc.push(desc),
+                Ok(None) => (),
+                Err(reason) => unsaved.push(format!("object {} ({})", index, reason)),
+            }
+        }
+
+        if !unsaved.is_empty() {
+            return Err(SceneError::Unsaved(unsaved));
+        }
+
+        Ok(SceneFile {
+            image: ImageDesc {
+                width: renderer.width(),
+                height: renderer.height(),
+                samples_per_pixel: renderer.samples_per_pixel(),
+            },
+            materials: materials.iter().map(MaterialDesc::from).collect(),
+            camera: CameraDesc::new(camera),
+            background: match renderer.background() {
+                Background::Sky => None,
+                Background::Color(color) => Some(color.into()),
+            },
+            world: world_desc,
+            lights: light_descs(world),
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+fn transformed<H: Hittable + Send + Sync>(
+    object: H,
+    transform: &TransformDesc,
+) -> Box<dyn Hittable + Send + Sync> {
+    if let Some(animated) = transform.to_animated_transform() {
+        return Box::new(Animated::new(object, animated));
+    }
+    match transform.to_transform() {
+        Some(transform) => Box::new(Transformed::new(object, transform)),
+        None => Box::new(object),
+    }
+}
+
+// Objects CSG can use as operands, the loader rejects the others.
+fn is_closed(desc: &HittableDesc) -> bool {
+    match desc {
+        HittableDesc::Sphere { radius, .. } => *radius >= 0.0,
+        HittableDesc::Cylinder { capped, .. } | HittableDesc::Cone { capped, .. } => *capped,
+        HittableDesc::Quad { .. }
+        | HittableDesc::Plane { .. }
+        | HittableDesc::Disk { .. }
+        | HittableDesc::Mesh { .. }
+        | HittableDesc::Heightfield { .. } => false,
+        _ => true,
+    }
+}
+
+// The object and where it is placed if it is a T, on its own or wrapped in a
+// transform or an animation.
+fn placed<T: Hittable>(object: &dyn Any) -> Option<(&T, Result<TransformDesc, String>)> {
+    if let Some(object) = object.downcast_ref::<T>() {
+        return Some((object, Ok(TransformDesc::default())));
+    }
+    if let Some(object) = object.downcast_ref::<Transformed<T>>() {
+        let transform = TransformDesc::from_transform(object.transform())
+            .ok_or_else(|| String::from("a transform that shears"));
+        return Some((object.object(), transform));
+    }
+    if let Some(object) = object.downcast_ref::<Animated<T>>() {
+        let transform = TransformDesc::from_animated(object.transform())
+            .ok_or_else(|| String::from("a motion outside of time 0 to 1"));
+        return Some((object.object(), transform));
+    }
+    None
+}
+
+// None for meshes that come with an earlier mesh of the same file, Err with
+// the reason for objects that can't be described.
+fn hittable_desc(
+    object: &dyn Any,
+    materials: &mut Vec<Material>,
+    directory: &Path,
+) -> Result<Option<HittableDesc>, String> {
+    let mut index_of = |material: Material| material_index(materials, material);
+
+    let desc = if let Some((sphere, transform)) = placed::<Sphere>(object) {
+        HittableDesc::Sphere {
+            position: sphere.location().into(),
+            radius: sphere.radius(),
+            end_position: None,
+            material: index_of(sphere.material()),
+            transform: transform?,
+        }
+    } else if let Some((sphere, transform)) = placed::<MovingSphere>(object) {
+        // Scene files move spheres from time 0 to 1.
+        HittableDesc::Sphere {
+            position: sphere.location(0.0).into(),
+            radius: sphere.radius(),
+            end_position: Some(sphere.location(1.0).into()),
+            material: index_of(sphere.material()),
+            transform: transform?,
+        }
+    } else if let Some((mesh, transform)) = placed::<Mesh>(object) {
+        // Model files are saved as the file, which brings back all its
+        // meshes.
+        let source = mesh.source().ok_or_else(|| {
+            format!(
+                "a mesh of {} faces that wasn't loaded from a file",
+                mesh.faces().len()
+            )
+        })?;
+        if source.part > 0 {
+            return Ok(None);
+        }
+        HittableDesc::Mesh {
+            file: relative_file(&source.file, directory),
+            material: source.material.map(&mut index_of),
+            transform: transform?,
+        }
+    } else if let Some((quad, transform)) = placed::<Quad>(object) {
+        let (u, v) = quad.sides();
+        HittableDesc::Quad {
+            corner: quad.corner().into(),
+            u: u.into(),
+            v: v.into(),
+            material: index_of(quad.material()),
+            transform: transform?,
+        }
+    } else if let Some((cuboid, transform)) = placed::<Cuboid>(object) {
+        HittableDesc::Cuboid {
+            minimum: cuboid.minimum().into(),
+            maximum: cuboid.maximum().into(),
+            material: index_of(cuboid.material()),
+            transform: transform?,
+        }
+    } else if let Some((plane, transform)) = placed::<Plane>(object) {
+        HittableDesc::Plane {
+            position: plane.point().into(),
+            normal: plane.normal().into(),
+            material: index_of(plane.material()),
+            transform: transform?,
+        }
+    } else if let Some((disk, transform)) = placed::<Disk>(object) {
+        HittableDesc::Disk {
+            position: disk.center().into(),
+            normal: disk.normal().into(),
+            radius: disk.radius(),
+            material: index_of(disk.material()),
+            transform: transform?,
+        }
+    } else if let Some((cylinder, transform)) = placed::<Cylinder>(object) {
+        HittableDesc::Cylinder {
+            position: cylinder.base().into(),
+            radius: cylinder.radius(),
+            height: cylinder.height(),
+            capped: cylinder.capped(),
+            material: index_of(cylinder.material()),
+            transform: transform?,
+        }
+    } else if let Some((cone, transform)) = placed::<Cone>(object) {
+        HittableDesc::Cone {
+            position: cone.base().into(),
+            radius: cone.radius(),
+            height: cone.height(),
+            capped: cone.capped(),
+            material: index_of(cone.material()),
+            transform: transform?,
+        }
+    } else if let Some((csg, transform)) = placed::<Csg>(object) {
+        let transform = transform?;
+        let left = hittable_desc(csg.left() as &dyn Any, materials, directory)?;
+        let right = hittable_desc(csg.right() as &dyn Any, materials, directory)?;
+        match (left, right) {
+            (Some(left), Some(right)) if is_closed(&left) && is_closed(&right) => {
+                HittableDesc::Csg {
+                    operation: csg.operation().into(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                    transform,
+                }
+            }
+            _ => {
+                return Err(String::from(
+                    "a CSG object with an operand that isn't closed",
+                ))
+            }
+        }
+    } else if let Some((heightfield, transform)) = placed::<Heightfield>(object) {
+        let source = heightfield.source().ok_or_else(|| {
+            format!(
+                "a heightfield of {}x{} heights that wasn't loaded from a file",
+                heightfield.columns(),
+                heightfield.rows()
+            )
+        })?;
+        HittableDesc::Heightfield {
+            file: relative_file(&source.file, directory),
+            position: heightfield.corner().into(),
+            size: heightfield.extent().into(),
+            columns: source.columns,
+            material: index_of(heightfield.material()),
+            transform: transform?,
+        }
+    } else {
+        return Err(String::from("an object of a type scene files don't have"));
+    };
+
+    Ok(Some(desc))
+}
+
+fn add_light(world: &mut World, desc: &LightDesc) {
+    match *desc {
+        LightDesc::Point {
+            position,
+            color,
+            intensity,
+        } => world.add_light(PointLight::new(position.into(), color.into(), intensity)),
+        LightDesc::Spot {
+            position,
+            direction,
+            color,
+            intensity,
+            cone_angle,
+            softness,
+        } => world.add_light(SpotLight::new(
+            position.into(),
+            direction.into(),
+            color.into(),
+            intensity,
+            cone_angle,
+            softness,
+        )),
+        LightDesc::Directional {
+            direction,
+            color,
+            intensity,
+            angular_diameter,
+        } => {
+            let mut sun = DirectionalLight::new(direction.into(), color.into(), intensity);
+            sun.angular_diameter = angular_diameter;
+            world.add_light(sun);
+        }
+    }
+}
+
+fn light_descs(world: &World) -> Vec<LightDesc> {
+    let mut lights = Vec::new();
+    for light in world.lights_of::<PointLight>() {
+        lights.push(LightDesc::Point {
+            position: light.position.into(),
+            color: light.color.into(),
+            intensity: light.intensity,
+        });
+    }
+    for light in world.lights_of::<SpotLight>() {
+        lights.push(LightDesc::Spot {
+            position: light.position.into(),
+            direction: light.direction.into(),
+            color: light.color.into(),
+            intensity: light.intensity,
+            cone_angle: light.cone_angle,
+            softness: light.softness,
+        });
+    }
+    for light in world.lights_of::<DirectionalLight>() {
+        lights.push(LightDesc::Directional {
+            direction: light.direction.into(),
+            color: light.color.into(),
+            intensity: light.intensity,
+            angular_diameter: light.angular_diameter,
+        });
+    }
+    lights
+}
+
+// Index of material in the materials table, adding it if it isn't there yet.
+fn material_index(materials: &mut Vec<Material>, material: Material) -> usize {
+    match materials.iter().position(|m| *m == material) {
+        Some(index) => index,
+        None => {
+            materials.push(material);
+            materials.len() - 1
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(Vec<Diagnostic>),
+    Obj(ObjError),
+    Gltf(GltfError),
+    Ply(PlyError),
+    Stl(StlError),
+    Heightfield(HeightfieldError),
+    Camera(String),
+    MaterialIndex { hittable: usize, material: usize },
+    // Objects the scene format can't describe.
+    Unsaved(Vec<String>),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Json(e) => write!(f, "{}", e),
+            Self::Obj(e) => write!(f, "{}", e),
+            Self::Gltf(e) => write!(f, "{}", e),
+            Self::Ply(e) => write!(f, "{}", e),
+            Self::Stl(e) => write!(f, "{}", e),
+            Self::Heightfield(e) => write!(f, "{}", e),
+            Self::Camera(message) => write!(f, "camera {}", message),
+            Self::Invalid(diagnostics) => {
+                for (index, diagnostic) in diagnostics.iter().enumerate() {
+                    if index > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            Self::MaterialIndex { hittable, material } => write!(
+                f,
+                "world[{}] references material {} which does not exist",
+                hittable, material
+            ),
+            Self::Unsaved(objects) => {
+                write!(f, "scene files can't describe {}", objects.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(e: std::io::Error) -> SceneError {
+        Self::Io(e)
+    }
+}
+
+impl From<ObjError> for SceneError {
+    fn from(e: ObjError) -> SceneError {
+        Self::Obj(e)
+    }
+}
+
+impl From<GltfError> for SceneError {
+    fn from(e: GltfError) -> SceneError {
+        Self::Gltf(e)
+    }
+}
+
+impl From<PlyError> for SceneError {
+    fn from(e: PlyError) -> SceneError {
+        Self::Ply(e)
+    }
+}
+
+impl From<StlError> for SceneError {
+    fn from(e: StlError) -> SceneError {
+        Self::Stl(e)
+    }
+}
+
+impl From<HeightfieldError> for SceneError {
+    fn from(e: HeightfieldError) -> SceneError {
+        Self::Heightfield(e)
+    }
+}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(e: serde_json::Error) -> SceneError {
+        Self::Json(e)
+    }
+}
+
+pub struct Scene {
+    pub camera: Box<dyn CameraModel>,
+    pub world: World,
+    pub renderer: Renderer,
+}
+
+impl Scene {
+    // Loads a scene file, or a whole glTF scene (.gltf, .glb) rendered with
+    // the default glTF image settings.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        if is_gltf(path) {
+            return Scene::load_gltf(path, GLTF_WIDTH, GLTF_SAMPLES_PER_PIXEL);
+        }
+
+        let text = fs::read_to_string(path)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        Scene::from_json_in(&text, directory)
+    }
+
+    // glTF files don't say how big the image is or how many samples to take.
+    pub fn load_gltf<P: AsRef<Path>>(
+        path: P,
+        width: usize,
+        samples_per_pixel: u32,
+    ) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let mut gltf = load_gltf(path)?;
+        let file = source_file(path);
+        for (part, gltf_mesh) in gltf.meshes.iter_mut().enumerate() {
+            gltf_mesh.mesh.set_source(MeshSource {
+                file: file.clone(),
+                part,
+                material: None,
+            });
+        }
+        Scene::from_gltf(gltf, width, samples_per_pixel)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SceneError> {
+        let path = path.as_ref();
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        fs::write(path, self.to_json_in(directory)?)?;
+        Ok(())
+    }
+
+    // The camera if it is a T.
+    pub fn camera_as<T: CameraModel>(&self) -> Option<&T> {
+        (&*self.camera as &dyn Any).downcast_ref::<T>()
+    }
+
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        self.to_json_in(Path::new(""))
+    }
+
+    // Files referenced by the scene, like meshes, are relative to directory.
+    pub fn to_json_in(&self, directory: &Path) -> Result<String, SceneError> {
+        SceneFile::new(&*self.camera, &self.world, &self.renderer, directory)?.to_json()
+    }
+
+    pub fn from_json(text: &str) -> Result<Scene, SceneError> {
+        Scene::from_json_in(text, Path::new(""))
+    }
+
+    // Files referenced by the scene, like meshes, are relative to directory.
+    pub fn from_json_in(text: &str, directory: &Path) -> Result<Scene, SceneError> {
+        let diagnostics = validate_scene(text);
+        if !diagnostics.is_empty() {
+            return Err(SceneError::Invalid(diagnostics));
+        }
+
+        let file: SceneFile = serde_json::from_str(text)?;
+        Scene::from_file(&file, directory)
+    }
+
+    pub fn from_file(file: &SceneFile, directory: &Path) -> Result<Scene, SceneError> {
+        let aspect_ratio = file.image.width as f64 / file.image.height as f64;
+
+        let camera = file.camera.to_camera(aspect_ratio)?;
+
+        let materials: Vec<Material> = file.materials.iter().map(Material::from).collect();
+
+        let mut world = World::new();
+        for (index, hittable) in file.world.iter().enumerate() {
+            for object in build_hittable(hittable, &materials, index, directory)? {
+                world.add_boxed(object);
+            }
+        }
+        for light in file.lights.iter() {
+            add_light(&mut world, light);
+        }
+
+        let mut renderer = Renderer::new(
+            file.image.width,
+            file.image.height,
+            file.image.samples_per_pixel,
+        );
+        if let Some(background) = file.background {
+            renderer.set_background(Background::Color(background.into()));
+        }
+
+        Ok(Scene {
+            camera,
+            world,
+            renderer,
+        })
+    }
+}
+
+impl CameraDesc {
+    // Cameras other than the ones of this crate are exported as a perspective
+    // camera with the default view.
+    pub fn new(camera: &dyn CameraModel) -> CameraDesc {
+        let any = camera as &dyn Any;
+        let mut desc = CameraDesc {
+            position: VectorDesc::from(Point3::new(0.0, 0.0, 0.0)),
+            look_at: VectorDesc::from(Point3::new(0.0, 0.0, -1.0)),
+            world_up: default_world_up(),
+            projection: None,
+            focal_length: Some(1.0),
+            vertical_fov: None,
+            horizontal_fov: None,
+            sensor: None,
+            view_height: None,
+            mapping: None,
+            shutter: None,
+            lens: None,
+        };
+        let mut view = |position: Point3, look_at: Point3, world_up: Vec3, shutter: (f64, f64)| {
+            desc.position = position.into();
+            desc.look_at = look_at.into();
+            desc.world_up = world_up.into();
+            desc.focal_length = None;
+            desc.shutter = match shutter.1 > shutter.0 {
+                true => Some(ShutterDesc {
+                    open: shutter.0,
+                    close: shutter.1,
+                }),
+                false => None,
+            };
+        };
+
+        if let Some(camera) = any.downcast_ref::<Camera>() {
+            let shutter = (camera.shutter_open, camera.shutter_close);
+            view(camera.position, camera.look_at, camera.world_up, shutter);
+            desc.focal_length = Some(camera.focal_length);
+            desc.lens = match camera.aperture > 0.0 {
+                true => Some(LensDesc {
+                    aperture: camera.aperture,
+                    focus_distance: Some(camera.focus_distance),
+                    blades: Some(camera.aperture_blades).filter(|b| *b >= 3),
+                }),
+                false => None,
+            };
+        } else if let Some(camera) = any.downcast_ref::<OrthographicCamera>() {
+            let shutter = (camera.shutter_open, camera.shutter_close);
+            view(camera.position, camera.look_at, camera.world_up, shutter);
+            desc.projection = Some(ProjectionDesc::Orthographic);
+            desc.view_height = Some(camera.view_height);
+        } else if let Some(camera) = any.downcast_ref::<FisheyeCamera>() {
+            let shutter = (camera.shutter_open, camera.shutter_close);
+            view(camera.position, camera.look_at, camera.world_up, shutter);
+            desc.projection = Some(ProjectionDesc::Fisheye);
+            match camera.is_horizontal() {
+                true => desc.horizontal_fov = Some(camera.fov),
+                false => desc.vertical_fov = Some(camera.fov),
+            }
+            desc.mapping = Some(camera.mapping.into());
+        } else if let Some(camera) = any.downcast_ref::<EquirectangularCamera>() {
+            let shutter = (camera.shutter_open, camera.shutter_close);
+            view(camera.position, camera.look_at, camera.world_up, shutter);
+            desc.projection = Some(ProjectionDesc::Equirectangular);
+        }
+
+        desc
+    }
+
+    pub fn to_camera(&self, aspect_ratio: f64) -> Result<Box<dyn CameraModel>, SceneError> {
+        let (position, look_at, world_up) = (
+            self.position.into(),
+            self.look_at.into(),
+            self.world_up.into(),
+        );
+        let (open, close) = match self.shutter {
+            Some(shutter) => (shutter.open, shutter.close),
+            None => (0.0, 0.0),
+        };
+        let views = (
+            self.focal_length,
+            self.vertical_fov,
+            self.horizontal_fov,
+            self.sensor,
+            self.view_height,
+        );
+
+        let camera: Box<dyn CameraModel> = match self.projection {
+            None | Some(ProjectionDesc::Perspective) => {
+                let mut camera = match views {
+                    (Some(focal_length), None, None, None, None) => {
+                        Camera::new(position, look_at, world_up, focal_length, aspect_ratio)
+                    }
+                    (None, Some(fov), None, None, None) => {
+                        Camera::with_vertical_fov(position, look_at, world_up, fov, aspect_ratio)
+                    }
+                    (None, None, Some(fov), None, None) => {
+                        Camera::with_horizontal_fov(position, look_at, world_up, fov, aspect_ratio)
+                    }
+                    (None, None, None, Some(sensor), None) => Camera::with_sensor(
+                        position,
+                        look_at,
+                        world_up,
+                        sensor.focal_length,
+                        sensor.width,
+                        aspect_ratio,
+                    ),
+                    _ => {
+                        return Err(camera_error(
+                            "needs one of focal_length, vertical_fov, horizontal_fov or sensor",
+                        ))
+                    }
+                };
+                (camera.shutter_open, camera.shutter_close) = (open, close);
+                if let Some(lens) = self.lens {
+                    camera.aperture = lens.aperture;
+                    if let Some(focus_distance) = lens.focus_distance {
+                        camera.focus_distance = focus_distance;
+                    }
+                    camera.aperture_blades = lens.blades.unwrap_or(0);
+                }
+                Box::new(camera)
+            }
+            Some(ProjectionDesc::Orthographic) => {
+                let mut camera = match views {
+                    (None, None, None, None, Some(view_height)) => OrthographicCamera::new(
+                        position,
+                        look_at,
+                        world_up,
+                        view_height,
+                        aspect_ratio,
+                    ),
+                    _ => return Err(camera_error("orthographic needs only view_height")),
+                };
+                (camera.shutter_open, camera.shutter_close) = (open, close);
+                Box::new(camera)
+            }
+            Some(ProjectionDesc::Fisheye) => {
+                let mapping = self
+                    .mapping
+                    .unwrap_or(FisheyeMappingDesc::Equidistant)
+                    .into();
+                let mut camera = match views {
+                    (None, Some(fov), None, None, None) => {
+                        FisheyeCamera::new(position, look_at, world_up, fov, mapping, aspect_ratio)
+                    }
+                    (None, None, Some(fov), None, None) => FisheyeCamera::with_horizontal_fov(
+                        position,
+                        look_at,
+                        world_up,
+                        fov,
+                        mapping,
+                        aspect_ratio,
+                    ),
+                    _ => {
+                        return Err(camera_error(
+                            "fisheye needs one of vertical_fov or horizontal_fov",
+                        ))
+                    }
+                };
+                (camera.shutter_open, camera.shutter_close) = (open, close);
+                Box::new(camera)
+            }
+            Some(ProjectionDesc::Equirectangular) => {
+                if !matches!(views, (None, None, None, None, None)) {
+                    return Err(camera_error("equirectangular has no field of view"));
+                }
+                let mut camera = EquirectangularCamera::new(position, look_at, world_up);
+                (camera.shutter_open, camera.shutter_close) = (open, close);
+                Box::new(camera)
+            }
+        };
+
+        Ok(camera)
+    }
+}
+
+fn camera_error(message: &str) -> SceneError {
+    SceneError::Camera(message.to_string())
+}
+
+impl Scene {
+    // Renders through the first camera of the file, or from the front of the
+    // meshes if it has none. The height follows the camera's aspect ratio.
+    pub fn from_gltf(
+        gltf: GltfScene,
+        width: usize,
+        samples_per_pixel: u32,
+    ) -> Result<Scene, SceneError> {
+        let mut world = World::new();
+        for gltf_mesh in gltf.meshes {
+            world.add_mesh(gltf_mesh.mesh);
+        }
+
+        let camera = match gltf.cameras.first() {
+            Some(camera) => camera.clone(),
+            None => {
+                let positions = world
+                    .objects_of::<Mesh>()
+                    .flat_map(|m| m.positions().iter());
+                let (mut lo, mut hi) = (Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0));
+                for (index, p) in positions.enumerate() {
+                    if index == 0 {
+                        (lo, hi) = (*p, *p);
+                    }
+                    lo = Point3::new(
+                        f64::min(lo.x, p.x),
+                        f64::min(lo.y, p.y),
+                        f64::min(lo.z, p.z),
+                    );
+                    hi = Point3::new(
+                        f64::max(hi.x, p.x),
+                        f64::max(hi.y, p.y),
+                        f64::max(hi.z, p.z),
+                    );
+                }
+                let center = (lo + hi) * 0.5;
+                let radius = f64::max((hi - lo).magnitude() * 0.5, 1.0);
+
+                GltfCamera {
+                    name: String::from("default"),
+                    position: center + Vec3::new(0.0, 0.0, 2.5 * radius),
+                    look_at: center,
+                    world_up: Vec3::new(0.0, 1.0, 0.0),
+                    focal_length: 1.0,
+                    aspect_ratio: None,
+                }
+            }
+        };
+
+        let aspect_ratio = camera.aspect_ratio.unwrap_or(4.0 / 3.0);
+        let height = usize::max((width as f64 / aspect_ratio).round() as usize, 1);
+
+        Ok(Scene {
+            camera: Box::new(camera.to_camera(aspect_ratio)),
+            world,
+            renderer: Renderer::new(width, height, samples_per_pixel),
+        })
+    }
+}
+
+// The objects described by hittable, a mesh file may hold several. index is
+// the position of the top level hittable in the world, for errors.
+fn build_hittable(
+    hittable: &HittableDesc,
+    materials: &[Material],
+    index: usize,
+    directory: &Path,
+) -> Result<Vec<Box<dyn Hittable + Send + Sync>>, SceneError> {
+    let material_at = |material: usize| {
+        materials
+            .get(material)
+            .copied()
+            .ok_or(SceneError::MaterialIndex {
+                hittable: index,
+                material,
+            })
+    };
+
+    let object = match hittable {
+        HittableDesc::Sphere {
+            position,
+            radius,
+            end_position: None,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let sphere = Sphere::new((*position).into(), *radius, material);
+            transformed(sphere, transform)
+        }
+        HittableDesc::Sphere {
+            position,
+            radius,
+            end_position: Some(end_position),
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let (start, end) = ((*position).into(), (*end_position).into());
+            let sphere = MovingSphere::new(start, end, 0.0, 1.0, *radius, material);
+            transformed(sphere, transform)
+        }
+        HittableDesc::Mesh {
+            file,
+            material,
+            transform,
+        } => {
+            let material = material.map(material_at).transpose()?;
+            let path = directory.join(file);
+            let mut objects = Vec::new();
+            for (part, mut mesh) in load_meshes(&path)?.into_iter().enumerate() {
+                if let Some(material) = material {
+                    mesh.set_material(material);
+                }
+                mesh.set_source(MeshSource {
+                    file: source_file(&path),
+                    part,
+                    material,
+                });
+                objects.push(transformed(mesh, transform));
+            }
+            return Ok(objects);
+        }
+        HittableDesc::Quad {
+            corner,
+            u,
+            v,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let quad = Quad::new((*corner).into(), (*u).into(), (*v).into(), material);
+            transformed(quad, transform)
+        }
+        HittableDesc::Cuboid {
+            minimum,
+            maximum,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let cuboid = Cuboid::new((*minimum).into(), (*maximum).into(), material);
+            transformed(cuboid, transform)
+        }
+        HittableDesc::Plane {
+            position,
+            normal,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let plane = Plane::new((*position).into(), (*normal).into(), material);
+            transformed(plane, transform)
+        }
+        HittableDesc::Disk {
+            position,
+            normal,
+            radius,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let disk = Disk::new((*position).into(), (*normal).into(), *radius, material);
+            transformed(disk, transform)
+        }
+        HittableDesc::Cylinder {
+            position,
+            radius,
+            height,
+            capped,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let cylinder = Cylinder::new((*position).into(), *radius, *height, *capped, material);
+            transformed(cylinder, transform)
+        }
+        HittableDesc::Cone {
+            position,
+            radius,
+            height,
+            capped,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let cone = Cone::new((*position).into(), *radius, *height, *capped, material);
+            transformed(cone, transform)
+        }
+        HittableDesc::Heightfield {
+            file,
+            position,
+            size,
+            columns,
+            material,
+            transform,
+        } => {
+            let material = material_at(*material)?;
+            let path = directory.join(file);
+            let (corner, extent) = ((*position).into(), (*size).into());
+            let mut heightfield = match columns {
+                Some(columns) => Heightfield::from_raw(&path, *columns, corner, extent, material)?,
+                None => Heightfield::from_image(&path, corner, extent, material)?,
+            };
+            heightfield.set_source(HeightfieldSource {
+                file: source_file(&path),
+                columns: *columns,
+            });
+            transformed(heightfield, transform)
+        }
+        HittableDesc::Csg {
+            operation,
+            left,
+            right,
+            transform,
+        } => {
+            // Only mesh files build into several objects or none, and
+            // they aren't operands.
+            let operand = |desc| -> Result<_, SceneError> {
+                Ok(build_hittable(desc, materials, index, directory)?
+                    .pop()
+                    .unwrap_or_else(|| Box::new(Mesh::new())))
+            };
+            let csg = Csg::from_boxed((*operation).into(), operand(left)?, operand(right)?);
+            transformed(csg, transform)
+        }
+    };
+
+    Ok(vec![object])
+}
+
+// Absolute, so saving can find the file from wherever the scene goes.
+fn source_file(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+// The way from directory to file, so saved scenes can be moved along with
+// their files. Absolute when they don't share a root, like on two drives.
+fn relative_file(file: &str, directory: &Path) -> String {
+    let directory = match directory.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => directory,
+    };
+    let (file, directory) = match (Path::new(file).is_absolute(), fs::canonicalize(directory)) {
+        (true, Ok(directory)) => (Path::new(file), directory),
+        _ => return file.to_string(),
+    };
+
+    let file_parts: Vec<_> = file.components().collect();
+    let directory_parts: Vec<_> = directory.components().collect();
+    let common = file_parts
+        .iter()
+        .zip(directory_parts.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return file.display().to_string();
+    }
+
+    let up = (common..directory_parts.len()).map(|_| String::from(".."));
+    let down = file_parts[common..]
+        .iter()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned());
+    up.chain(down).collect::<Vec<_>>().join("/")
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+}
+
+pub fn is_gltf<P: AsRef<Path>>(path: P) -> bool {
+    matches!(extension(path.as_ref()).as_deref(), Some("gltf" | "glb"))
+}
+
+// All meshes in a model file, the format is chosen by the file extension.
+fn load_meshes(path: &Path) -> Result<Vec<Mesh>, SceneError> {
+    match extension(path).as_deref() {
+        Some("gltf" | "glb") => Ok(load_gltf(path)?
+            .meshes
+            .into_iter()
+            .map(|m| m.mesh)
+            .collect()),
+        Some("ply") => Ok(vec![load_ply(path)?]),
+        Some("stl") => Ok(vec![load_stl(path)?]),
+        _ => Ok(load_obj(path)?.into_iter().map(|o| o.mesh).collect()),
+    }
+}
+
+#[test]
+fn load_test_scene() {
+    let scene = Scene::from_json(include_str!("../data/test.json")).unwrap();
+
+    assert_eq!(scene.renderer.width(), 800);
+    assert_eq!(scene.renderer.height(), 600);
+    assert_eq!(scene.renderer.samples_per_pixel(), 60);
+    let camera = scene.camera_as::<Camera>().unwrap();
+    assert_eq!(camera.position, Point3::new(0.0, 0.0, 1.0));
+    assert_eq!(camera.world_up, Vec3::new(0.0, 1.0, 0.0));
+    assert_eq!(camera.aspec_ratio, 800.0 / 600.0);
+}
+
+#[test]
+fn load_mesh_scene() {
+    let scene = Scene::load("data/mesh.json").unwrap();
+
+    assert_eq!(scene.world.objects_of::<Sphere>().count(), 1);
+    assert_eq!(scene.world.objects_of::<Mesh>().count(), 1);
+
+    let cube = scene.world.objects_of::<Mesh>().next().unwrap();
+    assert_eq!(cube.faces().len(), 12);
+    assert_eq!(cube.materials().len(), 2);
+    assert_eq!(
+        cube.materials()[0],
+        Material::Diffuse(Color::new(0.8, 0.1, 0.1))
+    );
+
+    // Saved as the file it came from, relative to where the scene goes.
+    let json = scene.to_json().unwrap();
+    assert!(json.contains(r#""file": "data/cube.obj""#));
+    let json_in_data = scene.to_json_in(Path::new("data")).unwrap();
+    assert!(json_in_data.contains(r#""file": "cube.obj""#));
+    let json_in_src = scene.to_json_in(Path::new("src")).unwrap();
+    assert!(json_in_src.contains(r#""file": "../data/cube.obj""#));
+    let saved = Scene::from_json(&json).unwrap();
+    let cube = saved.world.objects_of::<Mesh>().next().unwrap();
+    assert_eq!(cube.faces().len(), 12);
+    assert!(cube.source().unwrap().file.ends_with("cube.obj"));
+
+    // Meshes made in code can't be saved.
+    let mut scene = saved;
+    let triangle = Mesh::from_triangles(
+        vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ],
+        &[[0, 1, 2]],
+        DEFAULT_MATERIAL,
+    )
+    .unwrap();
+    scene.world.add_mesh(triangle);
+    assert!(matches!(scene.to_json(), Err(SceneError::Unsaved(_))));
+}
+
+#[test]
+fn load_gltf_scene() {
+    let text = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [ { "nodes": [0] } ],
+        "nodes": [ { "mesh": 0 } ],
+        "meshes": [ { "primitives": [ { "attributes": { "POSITION": 0 }, "indices": 1 } ] } ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "buffers": [ { "byteLength": 42, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIA" } ]
+    }"#;
+    let path = std::env::temp_dir().join("raycast_scene_triangle.gltf");
+    fs::write(&path, text).unwrap();
+
+    let scene = Scene::load(&path).unwrap();
+    assert_eq!(scene.world.objects_of::<Mesh>().count(), 1);
+    assert_eq!(scene.renderer.width(), GLTF_WIDTH);
+    assert_eq!(scene.renderer.samples_per_pixel(), GLTF_SAMPLES_PER_PIXEL);
+
+    // The height follows the default camera's 4:3.
+    let scene = Scene::load_gltf(&path, 320, 8).unwrap();
+    assert_eq!(scene.renderer.width(), 320);
+    assert_eq!(scene.renderer.height(), 240);
+    assert_eq!(scene.renderer.samples_per_pixel(), 8);
+}
+
+#[test]
+fn load_heightfield_scene() {
+    let directory = std::env::temp_dir().join("raycast_heightfield_scene");
+    fs::create_dir_all(&directory).unwrap();
+    let heights = [0.0f32, 1.0, 0.5, 0.25, 0.0, 0.75];
+    let bytes: Vec<u8> = heights.iter().flat_map(|h| h.to_le_bytes()).collect();
+    fs::write(directory.join("terrain.r32"), bytes).unwrap();
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [{ "material": "dielectric", "refraction_index": 1.5 }],
+        "camera": {
+            "position": { "x": 0, "y": 5, "z": 5 },
+            "look_at": { "x": 0, "y": 0, "z": 0 },
+            "focal_length": 1
+        },
+        "world": [
+            { "hittable": "heightfield", "file": "terrain.r32", "columns": 3, "material": 0,
+              "position": { "x": -1, "y": 0, "z": -1 }, "size": { "x": 2, "y": 4, "z": 2 },
+              "translate": { "x": 0, "y": 1, "z": 0 } }
+        ]
+    }"#;
+    fs::write(directory.join("terrain.json"), text).unwrap();
+
+    // Saved as the file it came from, with its columns and placement.
+    let scene = Scene::load(directory.join("terrain.json")).unwrap();
+    scene.save(directory.join("saved.json")).unwrap();
+    let json = fs::read_to_string(directory.join("saved.json")).unwrap();
+    assert!(json.contains(r#""file": "terrain.r32""#));
+    let saved = Scene::load(directory.join("saved.json")).unwrap();
+    let field = saved
+        .world
+        .objects_of::<Transformed<Heightfield>>()
+        .next()
+        .unwrap();
+    assert_eq!((field.object().columns(), field.object().rows()), (3, 2));
+    assert_eq!(field.object().height(2, 1), 0.75);
+    assert_eq!(field.object().source().unwrap().columns, Some(3));
+    assert_eq!(
+        field.bounding_box(),
+        Some(crate::aabb::Aabb::new(
+            Point3::new(-1.0, 1.0, -1.0),
+            Point3::new(1.0, 5.0, 1.0)
+        ))
+    );
+}
+
+#[test]
+fn load_transformed_objects() {
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [{ "material": "dielectric", "refraction_index": 1.5 }],
+        "camera": {
+            "position": { "x": 0, "y": 0, "z": 1 },
+            "look_at": { "x": 0, "y": 0, "z": 0 },
+            "focal_length": 1
+        },
+        "world": [
+            { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 0 },
+            { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 0,
+              "scale": { "x": 1, "y": 2, "z": 1 }, "rotate": { "x": 0, "y": 0, "z": 90 },
+              "translate": { "x": 10, "y": 0, "z": 0 } }
+        ]
+    }"#;
+
+    let scene = Scene::from_json(text).unwrap();
+    assert_eq!(scene.world.objects_of::<Sphere>().count(), 1);
+    let ellipsoid = scene
+        .world
+        .objects_of::<Transformed<Sphere>>()
+        .next()
+        .unwrap();
+
+    // Stretched along y, then turned to lie along x.
+    let aabb = ellipsoid.bounding_box().unwrap();
+    assert!(aabb.minimum.nearly_eq(Point3::new(8.0, -1.0, -1.0)));
+    assert!(aabb.maximum.nearly_eq(Point3::new(12.0, 1.0, 1.0)));
+
+    // Saved as the scale, rotation and translation it was loaded with.
+    let json = scene.to_json().unwrap();
+    assert!(json.contains(r#""z": 90.0"#));
+    let saved = Scene::from_json(&json).unwrap();
+    let saved_ellipsoid = saved
+        .world
+        .objects_of::<Transformed<Sphere>>()
+        .next()
+        .unwrap();
+    let (a, b) = (
+        saved_ellipsoid.transform().matrix().m,
+        ellipsoid.transform().matrix().m,
+    );
+    assert!((0..4).all(|i| (0..4).all(|j| (a[i][j] - b[i][j]).abs() < 1e-12)));
+
+    // Scaling after rotating shears, which scene files can't describe.
+    let mut scene = saved;
+    let shear = Transform::rotation(Vec3::new(0.0, 0.0, 1.0), 0.5)
+        .then(&Transform::scaling(Vec3::new(1.0, 3.0, 1.0)));
+    let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Material::Dielectric(1.5));
+    scene.world.add(Transformed::new(sphere, shear));
+    assert!(matches!(scene.to_json(), Err(SceneError::Unsaved(o)) if o.len() == 1));
+}
+
+#[test]
+fn load_csg_objects() {
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [{ "material": "dielectric", "refraction_index": 1.5 }],
+        "camera": {
+            "position": { "x": 0, "y": 0, "z": 1 },
+            "look_at": { "x": 0, "y": 0, "z": 0 },
+            "focal_length": 1
+        },
+        "world": [
+            { "hittable": "csg", "operation": "intersection",
+              "left": { "hittable": "sphere", "position": { "x": -0.5, "y": 0, "z": 0 }, "radius": 1, "material": 0 },
+              "right": { "hittable": "sphere", "position": { "x": 0.5, "y": 0, "z": 0 }, "radius": 1, "material": 0 },
+              "translate": { "x": 0, "y": 0, "z": -5 } }
+        ]
+    }"#;
+
+    let scene = Scene::from_json(text).unwrap();
+    let lens = scene.world.objects_of::<Transformed<Csg>>().next().unwrap();
+    assert_eq!(lens.object().operation(), CsgOperation::Intersection);
+
+    // The lens is 0.5 thick on either side of its center.
+    let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+    let spans = lens.spans(&ray).unwrap();
+    assert_eq!(spans.len(), 1);
+    assert!((spans[0].enter.solution - (5.0 - f64::sqrt(0.75))).abs() < 1e-9);
+    assert!((spans[0].exit.solution - (5.0 + f64::sqrt(0.75))).abs() < 1e-9);
+
+    let saved = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+    let lens = saved.world.objects_of::<Transformed<Csg>>().next().unwrap();
+    assert_eq!(lens.object().operation(), CsgOperation::Intersection);
+    let saved_spans = lens.spans(&ray).unwrap();
+    assert_eq!(saved_spans.len(), 1);
+    assert!((saved_spans[0].enter.solution - spans[0].enter.solution).abs() < 1e-12);
+
+    // Operands the loader would reject can't be saved.
+    let mut scene = saved;
+    let material = Material::Dielectric(1.5);
+    scene.world.add(Csg::new(
+        CsgOperation::Difference,
+        Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material),
+        Quad::xz((-1.0, 1.0), (-1.0, 1.0), 0.0, material),
+    ));
+    assert!(matches!(scene.to_json(), Err(SceneError::Unsaved(_))));
+}
+
+#[test]
+fn load_moving_objects_and_lens() {
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [{ "material": "dielectric", "refraction_index": 1.5 }],
+        "camera": {
+            "position": { "x": 0, "y": 0, "z": 1 },
+            "look_at": { "x": 0, "y": 0, "z": 0 },
+            "vertical_fov": 90,
+            "shutter": { "open": 0, "close": 0.5 },
+            "lens": { "aperture": 0.1, "blades": 5 }
+        },
+        "world": [
+            { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 0,
+              "end_position": { "x": 0, "y": 2, "z": 0 } },
+            { "hittable": "box", "minimum": { "x": 0, "y": 0, "z": 0 }, "maximum": { "x": 1, "y": 1, "z": 1 },
+              "material": 0, "translate": { "x": 5, "y": 0, "z": 0 },
+              "motion": { "rotate": { "x": 0, "y": 90, "z": 0 } } }
+        ]
+    }"#;
+
+    let scene = Scene::from_json(text).unwrap();
+    let camera = scene.camera_as::<Camera>().unwrap();
+    assert!((camera.focal_length - 0.5).abs() < 1e-12);
+    assert_eq!(camera.shutter_close, 0.5);
+    let sphere = scene.world.objects_of::<MovingSphere>().next().unwrap();
+    assert_eq!(sphere.location(1.0), Point3::new(0.0, 2.0, 0.0));
+
+    // The box turns in place, it keeps its translation.
+    let turning = scene.world.objects_of::<Animated<Cuboid>>().next().unwrap();
+    let corner = Point3::new(1.0, 0.0, 0.0);
+    let end = turning.transform().at(1.0).point(corner);
+    assert!(end.nearly_eq(Point3::new(5.0, 0.0, -1.0)));
+
+    let saved = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+    let camera = saved.camera_as::<Camera>().unwrap();
+    assert_eq!(camera.shutter_close, 0.5);
+    assert_eq!(camera.aperture, 0.1);
+    assert_eq!(camera.focus_distance, 1.0);
+    assert_eq!(camera.aperture_blades, 5);
+    assert!(saved
+        .world
+        .objects_of::<MovingSphere>()
+        .eq(scene.world.objects_of::<MovingSphere>()));
+    let turned = saved.world.objects_of::<Animated<Cuboid>>().next().unwrap();
+    for time in [0.0, 0.5, 1.0] {
+        let (a, b) = (
+            turned.transform().at(time).point(corner),
+            turning.transform().at(time).point(corner),
+        );
+        assert!((a - b).magnitude() < 1e-12);
+    }
+}
+
+#[test]
+fn load_projections() {
+    let load = |fields: &str| {
+        let text = format!(
+            r#"{{
+            "image": {{ "width": 8, "height": 4, "samples_per_pixel": 1 }},
+            "materials": [],
+            "camera": {{ "position": {{ "x": 0, "y": 0, "z": 1 }}, "look_at": {{ "x": 0, "y": 0, "z": 0 }}, {} }},
+            "world": []
+        }}"#,
+            fields
+        );
+        let scene = Scene::from_json(&text).unwrap();
+        let saved = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+        (scene, saved)
+    };
+
+    let (scene, saved) = load(r#""projection": "orthographic", "view_height": 3"#);
+    let camera = scene.camera_as::<OrthographicCamera>().unwrap();
+    assert_eq!((camera.view_height, camera.aspec_ratio), (3.0, 2.0));
+    assert_eq!(
+        saved.camera_as::<OrthographicCamera>().unwrap().view_height,
+        3.0
+    );
+
+    let (scene, saved) = load(
+        r#""projection": "fisheye", "horizontal_fov": 200, "mapping": "equisolid",
+           "shutter": { "open": 0, "close": 1 }"#,
+    );
+    let camera = scene.camera_as::<FisheyeCamera>().unwrap();
+    assert_eq!(camera.mapping, FisheyeMapping::Equisolid);
+    assert!(camera.is_horizontal());
+    let camera = saved.camera_as::<FisheyeCamera>().unwrap();
+    assert_eq!((camera.fov, camera.shutter_close), (200.0, 1.0));
+    assert!(camera.is_horizontal());
+
+    let (_, saved) = load(r#""projection": "equirectangular""#);
+    let camera = saved.camera_as::<EquirectangularCamera>().unwrap();
+    assert_eq!(camera.position, Point3::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn load_cornell_box() {
+    let scene = Scene::from_json(include_str!("../data/cornell.json")).unwrap();
+    let black = Color::new(0.0, 0.0, 0.0);
+    assert_eq!(scene.renderer.background(), Background::Color(black));
+
+    // The lamp shines down into the box.
+    let lamp = scene
+        .world
+        .objects_of::<Quad>()
+        .find(|q| matches!(q.material(), Material::Emissive(..)))
+        .unwrap();
+    let ray = Ray::new(Point3::new(278.0, 100.0, 279.0), Vec3::new(0.0, 1.0, 0.0));
+    let hit = lamp.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hit.material.emitted(&hit), Color::new(15.0, 15.0, 15.0));
+    let ray = Ray::new(Point3::new(278.0, 600.0, 279.0), Vec3::new(0.0, -1.0, 0.0));
+    let hit = lamp.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hit.material.emitted(&hit), black);
+
+    let saved = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+    assert_eq!(saved.renderer.background(), Background::Color(black));
+    assert!(saved
+        .world
+        .objects_of::<Quad>()
+        .eq(scene.world.objects_of::<Quad>()));
+}
+
+#[test]
+fn load_lights() {
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [],
+        "camera": { "position": { "x": 0, "y": 0, "z": 1 }, "look_at": { "x": 0, "y": 0, "z": 0 }, "focal_length": 1 },
+        "world": [],
+        "lights": [
+            { "light": "point", "position": { "x": 0, "y": 5, "z": 0 }, "color": { "r": 1, "g": 1, "b": 1 },
+              "intensity": 50 },
+            { "light": "spot", "position": { "x": 0, "y": 5, "z": 0 }, "direction": { "x": 0, "y": -1, "z": 0 },
+              "color": { "r": 1, "g": 0.9, "b": 0.8 }, "intensity": 80, "cone_angle": 40, "softness": 0.3 },
+            { "light": "directional", "direction": { "x": -1, "y": -2, "z": 0 },
+              "color": { "r": 1, "g": 1, "b": 1 }, "intensity": 3, "angular_diameter": 0.5 }
+        ]
+    }"#;
+
+    let scene = Scene::from_json(text).unwrap();
+    assert_eq!(scene.world.lights().count(), 3);
+    let spot = scene.world.lights_of::<SpotLight>().next().unwrap();
+    assert_eq!((spot.cone_angle, spot.softness), (40.0, 0.3));
+
+    let saved = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+    assert!(saved
+        .world
+        .lights_of::<PointLight>()
+        .eq(scene.world.lights_of::<PointLight>()));
+    assert!(saved
+        .world
+        .lights_of::<SpotLight>()
+        .eq(scene.world.lights_of::<SpotLight>()));
+    assert!(saved
+        .world
+        .lights_of::<DirectionalLight>()
+        .eq(scene.world.lights_of::<DirectionalLight>()));
+}
+
+#[test]
+fn bad_material_index() {
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [],
+        "camera": {
+            "position": { "x": 0, "y": 0, "z": 1 },
+            "look_at": { "x": 0, "y": 0, "z": 0 },
+            "focal_length": 1
+        },
+        "world": [
+            { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 3 }
+        ]
+    }"#;
+
+    match Scene::from_json(text) {
+        Err(SceneError::Invalid(diagnostics)) => {
+            assert_eq!(diagnostics.len(), 1);
+            assert_eq!(diagnostics[0].path, "world[0].material");
+        }
+        _ => panic!("expected a material index error"),
+    }
+
+    let file: SceneFile = serde_json::from_str(text).unwrap();
+    match Scene::from_file(&file, Path::new("")) {
+        Err(SceneError::MaterialIndex { hittable, material }) => {
+            assert_eq!(hittable, 0);
+            assert_eq!(material, 3);
+        }
+        _ => panic!("expected a material index error"),
+    }
+}
+
+#[test]
+fn save_and_load_scene() {
+    use rand::prelude::*;
+
+    // Random values, every f64 has to survive the trip through JSON.
+    let mut rng = thread_rng();
+    let mut random_point = || {
+        Point3::new(
+            rng.gen_range(-10.0..10.0),
+            rng.gen_range(-10.0..10.0),
+            rng.gen_range(-10.0..10.0),
+        )
+    };
+    let diffuse = Material::Diffuse(Color::random_color());
+    let metalic = Material::Metalic(Color::random_color(), random::<f64>());
+    let dielectric = Material::Dielectric(1.0 + random::<f64>());
+
+    let mut world = World::new();
+    world.add_sphere(Sphere::new(random_point(), random::<f64>(), diffuse));
+    world.add_sphere(Sphere::new(random_point(), random::<f64>(), metalic));
+    world.add_sphere(Sphere::new(random_point(), -random::<f64>(), diffuse));
+    let (a, b) = (random_point(), random_point());
+    world.add(Quad::xz((a.x, a.x + 1.0), (a.z, a.z + 1.0), b.y, diffuse));
+    world.add(Cuboid::new(a, a + Color::random_color(), dielectric));
+    world.add(Plane::new(b, random_point().unit(), dielectric));
+    world.add(Cylinder::new(
+        random_point(),
+        random::<f64>(),
+        random::<f64>() + 1.0,
+        false,
+        dielectric,
+    ));
+
+    let scene = Scene {
+        camera: Box::new(Camera::new(
+            random_point(),
+            random_point(),
+            Vec3::new(0.0, 1.0, 0.0),
+            random::<f64>() + 1.0,
+            16.0 / 9.0,
+        )),
+        world,
+        renderer: Renderer::new(160, 90, 7),
+    };
+
+    let file =
+        SceneFile::new(&*scene.camera, &scene.world, &scene.renderer, Path::new("")).unwrap();
+    assert_eq!(file.materials.len(), 3);
+
+    let loaded = Scene::from_json(&scene.to_json().unwrap()).unwrap();
+    assert!(loaded
+        .world
+        .objects_of::<Sphere>()
+        .eq(scene.world.objects_of::<Sphere>()));
+    assert!(loaded
+        .world
+        .objects_of::<Quad>()
+        .eq(scene.world.objects_of::<Quad>()));
+    assert!(loaded
+        .world
+        .objects_of::<Cuboid>()
+        .eq(scene.world.objects_of::<Cuboid>()));
+    assert!(loaded
+        .world
+        .objects_of::<Plane>()
+        .eq(scene.world.objects_of::<Plane>()));
+    assert!(loaded
+        .world
+        .objects_of::<Cylinder>()
+        .eq(scene.world.objects_of::<Cylinder>()));
+    let (loaded_camera, camera) = (
+        loaded.camera_as::<Camera>().unwrap(),
+        scene.camera_as::<Camera>().unwrap(),
+    );
+    assert_eq!(loaded_camera.position, camera.position);
+    assert_eq!(loaded_camera.look_at, camera.look_at);
+    assert_eq!(loaded_camera.world_up, camera.world_up);
+    assert_eq!(loaded_camera.focal_length, camera.focal_length);
+    assert_eq!(loaded_camera.aspec_ratio, camera.aspec_ratio);
+    assert_eq!(loaded.renderer.samples_per_pixel(), 7);
+}
+
+#[test]
+fn load_glass() {
+    let text = r#"{
+        "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+        "materials": [
+            { "material": "dielectric", "refraction_index": 1.5 },
+            { "material": "dielectric", "refraction_index": 1.5, "roughness": 0.3,
+              "absorption": { "color": { "r": 0.5, "g": 0.8, "b": 1 }, "distance": 2 } }
+        ],
+        "camera": { "position": { "x": 0, "y": 0, "z": 5 }, "look_at": { "x": 0, "y": 0, "z": 0 }, "focal_length": 1 },
+        "world": [
+            { "hittable": "sphere", "position": { "x": -1, "y": 0, "z": 0 }, "radius": 1, "material": 0 },
+            { "hittable": "sphere", "position": { "x": 1, "y": 0, "z": 0 }, "radius": 1, "material": 1 }
+        ]
+    }"#;
+
+    let scene = Scene::from_json(text).unwrap();
+    let materials: Vec<Material> = scene
+        .world
+        .objects_of::<Sphere>()
+        .map(|s| s.material())
+        .collect();
+    assert!(materials.contains(&Material::Dielectric(1.5)));
+    let frosted = Material::Glass {
+        refraction_index: 1.5,
+        roughness: 0.3,
+        color: Color::new(0.5, 0.8, 1.0),
+        distance: 2.0,
+    };
+    assert!(materials.contains(&frosted));
+
+    // Clear glass is saved as before.
+    let json = scene.to_json().unwrap();
+    assert_eq!(json.matches("roughness").count(), 1);
+    let saved = Scene::from_json(&json).unwrap();
+    assert!(saved
+        .world
+        .objects_of::<Sphere>()
+        .any(|s| s.material() == frosted));
+}
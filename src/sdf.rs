@@ -0,0 +1,420 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::vec3::*;
+
+const MAX_STEPS: usize = 512;
+// Close enough to the surface to count as a hit.
+const SURFACE_DISTANCE: f64 = 1e-4;
+
+// Signed distance function, negative inside. Sphere tracing steps by the
+// distance, so it may underestimate but must not overestimate. Closures
+// taking a point are distance functions too.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Point3) -> f64;
+
+    fn translate(self, offset: Vec3) -> Translate<Self>
+    where
+        Self: Sized,
+    {
+        Translate { sdf: self, offset }
+    }
+
+    fn round(self, radius: f64) -> Round<Self>
+    where
+        Self: Sized,
+    {
+        Round { sdf: self, radius }
+    }
+
+    fn twist(self, rate: f64) -> Twist<Self>
+    where
+        Self: Sized,
+    {
+        Twist { sdf: self, rate }
+    }
+
+    fn repeat(self, period: Vec3) -> Repeat<Self>
+    where
+        Self: Sized,
+    {
+        Repeat { sdf: self, period }
+    }
+
+    fn smooth_union<O: Sdf>(self, other: O, smoothness: f64) -> SmoothUnion<Self, O>
+    where
+        Self: Sized,
+    {
+        SmoothUnion {
+            a: self,
+            b: other,
+            smoothness,
+        }
+    }
+}
+
+impl<F> Sdf for F
+where
+    F: Fn(Point3) -> f64 + Send + Sync,
+{
+    fn distance(&self, p: Point3) -> f64 {
+        self(p)
+    }
+}
+
+// Primitives, all centered on the origin.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SdfSphere {
+    pub radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(radius: f64) -> SdfSphere {
+        SdfSphere { radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Point3) -> f64 {
+        p.magnitude() - self.radius
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SdfBox {
+    pub half_size: Vec3,
+}
+
+impl SdfBox {
+    pub fn new(half_size: Vec3) -> SdfBox {
+        SdfBox { half_size }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Point3) -> f64 {
+        let q = Vec3::new(
+            p.x.abs() - self.half_size.x,
+            p.y.abs() - self.half_size.y,
+            p.z.abs() - self.half_size.z,
+        );
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+        outside.magnitude() + f64::min(q.x.max(q.y).max(q.z), 0.0)
+    }
+}
+
+// Ring around the y axis.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SdfTorus {
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(major_radius: f64, minor_radius: f64) -> SdfTorus {
+        SdfTorus {
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Point3) -> f64 {
+        let ring = f64::hypot(p.x, p.z) - self.major_radius;
+        f64::hypot(ring, p.y) - self.minor_radius
+    }
+}
+
+// The Mandelbulb fractal, an estimate of the distance that fits within a
+// radius of about 1.2 for power 8.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mandelbulb {
+    pub power: f64,
+    pub iterations: usize,
+}
+
+impl Mandelbulb {
+    pub fn new(power: f64, iterations: usize) -> Mandelbulb {
+        Mandelbulb { power, iterations }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Point3) -> f64 {
+        let mut z = p;
+        let mut derivative = 1.0;
+        let mut r = z.magnitude();
+
+        for _ in 0..self.iterations {
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+
+            let theta = f64::acos(z.z / r) * self.power;
+            let phi = f64::atan2(z.y, z.x) * self.power;
+            derivative = r.powf(self.power - 1.0) * self.power * derivative + 1.0;
+
+            let zr = r.powf(self.power);
+            z = Vec3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ) * zr
+                + p;
+            r = z.magnitude();
+        }
+
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / derivative
+    }
+}
+
+// Combinators, made with the methods of Sdf.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Translate<S> {
+    sdf: S,
+    offset: Vec3,
+}
+
+impl<S: Sdf> Sdf for Translate<S> {
+    fn distance(&self, p: Point3) -> f64 {
+        self.sdf.distance(p - self.offset)
+    }
+}
+
+// Grows the surface by radius, rounding its edges.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Round<S> {
+    sdf: S,
+    radius: f64,
+}
+
+impl<S: Sdf> Sdf for Round<S> {
+    fn distance(&self, p: Point3) -> f64 {
+        self.sdf.distance(p) - self.radius
+    }
+}
+
+// Turns by rate radians per unit along y. This stretches distances, trace
+// twisted objects with a step scale below 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Twist<S> {
+    sdf: S,
+    rate: f64,
+}
+
+impl<S: Sdf> Sdf for Twist<S> {
+    fn distance(&self, p: Point3) -> f64 {
+        let (sin, cos) = f64::sin_cos(-self.rate * p.y);
+        let q = Point3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+        self.sdf.distance(q)
+    }
+}
+
+// Copies repeating every period along each axis, a zero period doesn't
+// repeat on that axis. Copies should fit within their cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Repeat<S> {
+    sdf: S,
+    period: Vec3,
+}
+
+impl<S: Sdf> Sdf for Repeat<S> {
+    fn distance(&self, p: Point3) -> f64 {
+        let cell = |x: f64, period: f64| match period == 0.0 {
+            true => x,
+            false => x - period * (x / period).round(),
+        };
+        let q = Point3::new(
+            cell(p.x, self.period.x),
+            cell(p.y, self.period.y),
+            cell(p.z, self.period.z),
+        );
+        self.sdf.distance(q)
+    }
+}
+
+// Union blending the two surfaces where they are closer than smoothness.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmoothUnion<A, B> {
+    a: A,
+    b: B,
+    smoothness: f64,
+}
+
+impl<A: Sdf, B: Sdf> Sdf for SmoothUnion<A, B> {
+    fn distance(&self, p: Point3) -> f64 {
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        let k = self.smoothness;
+        if k <= 0.0 {
+            return f64::min(a, b);
+        }
+        let h = f64::clamp(0.5 + 0.5 * (b - a) / k, 0.0, 1.0);
+        b + (a - b) * h - k * h * (1.0 - h)
+    }
+}
+
+// Distance function rendered by sphere tracing within bounds, which must
+// contain the whole surface. The bounds are also its box in the BVH.
+pub struct SdfHittable<S: Sdf> {
+    sdf: S,
+    bounds: Aabb,
+    material: Material,
+    step_scale: f64,
+}
+
+impl<S: Sdf> SdfHittable<S> {
+    pub fn new(sdf: S, bounds: Aabb, material: Material) -> SdfHittable<S> {
+        SdfHittable {
+            sdf,
+            bounds,
+            material,
+            step_scale: 1.0,
+        }
+    }
+
+    pub fn sdf(&self) -> &S {
+        &self.sdf
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    // Fraction of the distance taken by each step, below 1 for distance
+    // functions that overestimate.
+    pub fn set_step_scale(&mut self, step_scale: f64) {
+        self.step_scale = step_scale;
+    }
+
+    fn hit_at(&self, ray: &Ray, solution: f64) -> Option<Hit> {
+        let point = ray.at(solution);
+        let normal = self.normal(point);
+        if normal.nearly_zero() {
+            return None;
+        }
+
+        let normal = normal.unit();
+        let front = ray.direction.dot(normal) < 0.0;
+        let mut hit = Hit::new(point, normal, solution, front, self.material);
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+
+    // Central differences, not normalized.
+    fn normal(&self, p: Point3) -> Vec3 {
+        let h = SURFACE_DISTANCE;
+        let difference =
+            |offset: Vec3| self.sdf.distance(p + offset) - self.sdf.distance(p - offset);
+        Vec3::new(
+            difference(Vec3::new(h, 0.0, 0.0)),
+            difference(Vec3::new(0.0, h, 0.0)),
+            difference(Vec3::new(0.0, 0.0, h)),
+        )
+    }
+}
+
+impl<S: Sdf + 'static> Hittable for SdfHittable<S> {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let inverse_direction = Vec3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let (mut solution, exit) =
+            self.bounds
+                .clip(ray.origin, inverse_direction, minimum, maximum)?;
+        let length = ray.direction.magnitude();
+
+        // Rays scattered off the surface start on it, they only hit once they
+        // got away from it.
+        let mut away = solution > minimum;
+        for _ in 0..MAX_STEPS {
+            let distance = self.sdf.distance(ray.at(solution)).abs();
+            if distance < SURFACE_DISTANCE && away {
+                return self.hit_at(ray, solution);
+            }
+            away |= distance >= SURFACE_DISTANCE;
+
+            solution += f64::max(distance * self.step_scale, SURFACE_DISTANCE) / length;
+            if solution > exit {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[test]
+fn hit_sdf_sphere() {
+    let bounds = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+    let sphere = SdfHittable::new(SdfSphere::new(1.0), bounds, Material::Dielectric(1.5));
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0));
+    let hit = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert!(hit.front);
+    assert!((hit.solution - 2.0).abs() < 1e-3);
+    assert!(hit.normal.nearly_eq(Vec3::new(0.0, 0.0, 1.0)));
+
+    // Leaving the surface into the sphere hits its far side from inside.
+    let ray = Ray::new(hit.point, Vec3::new(0.0, 0.0, -1.0));
+    let hit = sphere.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!(!hit.front);
+    assert!((hit.point.z + 1.0).abs() < 1e-3);
+    assert!(hit.normal.nearly_eq(Vec3::new(0.0, 0.0, 1.0)));
+
+    let ray = Ray::new(Point3::new(0.0, 1.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(sphere.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
+
+#[test]
+fn sdf_combinators() {
+    let p = Point3::new(0.3, 0.4, 0.0);
+    let cube = SdfBox::new(Vec3::new(1.0, 1.0, 1.0));
+    assert_eq!(cube.distance(Point3::new(3.0, 0.0, 0.0)), 2.0);
+    assert_eq!(
+        cube.clone().round(0.5).distance(Point3::new(3.0, 0.0, 0.0)),
+        1.5
+    );
+
+    let repeated = SdfSphere::new(0.5).repeat(Vec3::new(2.0, 0.0, 0.0));
+    assert!((repeated.distance(p) - repeated.distance(p + Vec3::new(4.0, 0.0, 0.0))).abs() < 1e-12);
+    assert!(repeated.distance(p + Vec3::new(0.0, 4.0, 0.0)) > 3.0);
+
+    // A quarter turn at height 1 takes x to z.
+    let twisted = SdfBox::new(Vec3::new(1.0, 2.0, 1.0))
+        .translate(Vec3::new(3.0, 0.0, 0.0))
+        .twist(std::f64::consts::FRAC_PI_2);
+    assert!(twisted.distance(Point3::new(0.0, 1.0, 3.0)) < 0.0);
+    assert!(twisted.distance(Point3::new(3.0, 1.0, 0.0)) > 0.0);
+
+    // Blending only lowers the distance where both are close.
+    let a = SdfSphere::new(1.0).translate(Vec3::new(-1.1, 0.0, 0.0));
+    let b = SdfSphere::new(1.0).translate(Vec3::new(1.1, 0.0, 0.0));
+    let blend = a.smooth_union(b, 0.5);
+    assert!(blend.distance(Point3::new(0.0, 0.0, 0.0)) < 0.0);
+    assert!((blend.distance(Point3::new(-3.1, 0.0, 0.0)) - 1.0).abs() < 1e-12);
+
+    let closure = |p: Point3| p.y;
+    assert_eq!(closure.distance(p), 0.4);
+    assert!(Mandelbulb::new(8.0, 8).distance(Point3::new(0.0, 0.0, 3.0)) > 1.0);
+}
@@ -1,9 +1,10 @@
+use super::aabb::*;
 use super::hittable::*;
 use super::material::*;
 use super::ray::*;
 use super::vec3::*;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Sphere {
     location: Point3,
     radius: f64,
@@ -22,6 +23,18 @@ impl Sphere {
             one_over_radius,
         }
     }
+
+    pub fn location(&self) -> Point3 {
+        self.location
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
 }
 
 impl Hittable for Sphere {
@@ -57,12 +70,113 @@ impl Hittable for Sphere {
             self.material,
         );
 
+        hit.uv = (
+            0.5 + f64::atan2(-normal.z, normal.x) / (2.0 * PI),
+            f64::acos(-normal.y) / PI,
+        );
+
         if !hit.front {
             hit.normal = -hit.normal;
         }
 
         Some(hit)
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // Negative radii make hollow spheres.
+        let r = f64::abs(self.radius);
+        let r = Vec3::new(r, r, r);
+        Some(Aabb::new(self.location - r, self.location + r))
+    }
+
+    // Hollow spheres are open, their inside reaches to infinity.
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        match self.radius > 0.0 {
+            true => Some(convex_spans(self, ray)),
+            false => None,
+        }
+    }
+}
+
+// Sphere moving in a straight line from start at time0 to end at time1. It
+// stays at either end outside of that interval.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovingSphere {
+    start: Point3,
+    end: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Material,
+}
+
+impl MovingSphere {
+    pub fn new(
+        start: Point3,
+        end: Point3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Material,
+    ) -> MovingSphere {
+        MovingSphere {
+            start,
+            end,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn start(&self) -> Point3 {
+        self.start
+    }
+
+    pub fn end(&self) -> Point3 {
+        self.end
+    }
+
+    pub fn times(&self) -> (f64, f64) {
+        (self.time0, self.time1)
+    }
+
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+
+    pub fn location(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.start;
+        }
+        let t = f64::clamp((time - self.time0) / (self.time1 - self.time0), 0.0, 1.0);
+        self.start + (self.end - self.start) * t
+    }
+
+    // Where the sphere is at time.
+    pub fn at(&self, time: f64) -> Sphere {
+        Sphere::new(self.location(time), self.radius, self.material)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        self.at(ray.time).hit(ray, minimum, maximum)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let start = self.at(self.time0).bounding_box()?;
+        let end = self.at(self.time1).bounding_box()?;
+        Some(start.union(&end))
+    }
+
+    fn spans(&self, ray: &Ray) -> Option<Vec<Span>> {
+        self.at(ray.time).spans(ray)
+    }
 }
 
 #[test]
@@ -98,3 +212,32 @@ fn hit_sphere() {
 
     assert!(hit.is_none());
 }
+
+#[test]
+fn hit_moving_sphere() {
+    let sphere = MovingSphere::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(2.0, 0.0, 0.0),
+        1.0,
+        2.0,
+        0.5,
+        Material::Dielectric(1.5),
+    );
+    assert_eq!(
+        sphere.bounding_box(),
+        Some(Aabb::new(
+            Point3::new(-0.5, -0.5, -0.5),
+            Point3::new(2.5, 0.5, 0.5)
+        ))
+    );
+    assert_eq!(sphere.location(0.0), Point3::new(0.0, 0.0, 0.0));
+    assert_eq!(sphere.location(3.0), Point3::new(2.0, 0.0, 0.0));
+
+    let direction = Vec3::new(0.0, 0.0, -1.0);
+    let ray = Ray::with_time(Point3::new(1.0, 0.0, 5.0), direction, 1.5);
+    let hit = sphere.hit(&ray, 0.0, f64::INFINITY).unwrap();
+    assert_eq!(hit.solution, 4.5);
+
+    let ray = Ray::with_time(Point3::new(1.0, 0.0, 5.0), direction, 1.0);
+    assert!(sphere.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
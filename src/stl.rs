@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use super::mesh::*;
+use super::vec3::*;
+
+// STL import, binary and ASCII. STL stores every triangle with its own
+// corners, identical corners are merged so the mesh shares them. The facet
+// normals in the file are ignored in favour of the triangle winding.
+
+#[derive(Debug)]
+pub struct StlError {
+    pub file: String,
+    pub message: String,
+}
+
+impl fmt::Display for StlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+impl std::error::Error for StlError {}
+
+pub fn load_stl<P: AsRef<Path>>(path: P) -> Result<Mesh, StlError> {
+    let path = path.as_ref();
+    let filename = path.display().to_string();
+    let error = |e: std::io::Error| StlError {
+        file: filename.clone(),
+        message: e.to_string(),
+    };
+
+    let file = File::open(path).map_err(error)?;
+    let length = file.metadata().map_err(error)?.len();
+    parse_stl(BufReader::new(file), &filename, Some(length))
+}
+
+// The file length, if known, tells binary files that happen to start with
+// "solid" apart from ASCII ones.
+pub fn parse_stl<R: BufRead>(
+    mut reader: R,
+    filename: &str,
+    length: Option<u64>,
+) -> Result<Mesh, StlError> {
+    let error = |message: String| StlError {
+        file: filename.to_string(),
+        message,
+    };
+
+    let mut header = [0u8; 84];
+    let read = read_up_to(&mut reader, &mut header).map_err(|e| error(e.to_string()))?;
+    let header = &header[..read];
+
+    let binary = match (read, length) {
+        (84, Some(length)) => {
+            let count = u32::from_le_bytes([header[80], header[81], header[82], header[83]]);
+            length == 84 + 50 * count as u64 || !looks_like_ascii(header)
+        }
+        _ => !looks_like_ascii(header),
+    };
+
+    let mut welder = Welder::new();
+    match binary {
+        true => read_binary(&mut reader, header, &mut welder).map_err(error)?,
+        false => read_ascii(header.chain(reader), &mut welder).map_err(error)?,
+    }
+
+    Ok(welder.mesh)
+}
+
+fn read_up_to<R: Read>(reader: &mut R, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn looks_like_ascii(header: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(header);
+    text.trim_start().starts_with("solid")
+        && (text.contains("facet") || text.contains("endsolid") || header.len() < 84)
+}
+
+fn read_binary<R: Read>(reader: &mut R, header: &[u8], welder: &mut Welder) -> Result<(), String> {
+    if header.len() < 84 {
+        return Err(String::from("truncated binary STL header"));
+    }
+    let count = u32::from_le_bytes([header[80], header[81], header[82], header[83]]);
+
+    let mut record = [0u8; 50];
+    for triangle in 0..count {
+        reader
+            .read_exact(&mut record)
+            .map_err(|_| format!("truncated at triangle {} of {}", triangle, count))?;
+
+        let float = |i: usize| {
+            let b = &record[i * 4..i * 4 + 4];
+            f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64
+        };
+        // Floats 0..3 are the facet normal, 2 bytes of attributes follow the
+        // corners.
+        let corner = |c: usize| Point3::new(float(3 + c * 3), float(4 + c * 3), float(5 + c * 3));
+        welder
+            .add_triangle([corner(0), corner(1), corner(2)])
+            .map_err(|e| format!("triangle {}: {}", triangle, e))?;
+    }
+
+    Ok(())
+}
+
+fn read_ascii<R: BufRead>(reader: R, welder: &mut Welder) -> Result<(), String> {
+    let mut corners = Vec::<Point3>::with_capacity(3);
+
+    for (index, line) in reader.lines().enumerate() {
+        let number = index + 1;
+        let line = line.map_err(|e| format!("line {}: {}", number, e))?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["vertex", x, y, z] => {
+                let parse = |word: &str| {
+                    word.parse::<f64>()
+                        .ok()
+                        .filter(|n| n.is_finite())
+                        .ok_or_else(|| {
+                            format!("line {}: expected a number, found `{}`", number, word)
+                        })
+                };
+                corners.push(Point3::new(parse(x)?, parse(y)?, parse(z)?));
+            }
+            ["vertex", ..] => return Err(format!("line {}: malformed vertex", number)),
+            ["endloop"] => {
+                if corners.len() != 3 {
+                    return Err(format!(
+                        "line {}: facet has {} vertices, expected 3",
+                        number,
+                        corners.len()
+                    ));
+                }
+                welder
+                    .add_triangle([corners[0], corners[1], corners[2]])
+                    .map_err(|e| format!("line {}: {}", number, e))?;
+                corners.clear();
+            }
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+struct Welder {
+    mesh: Mesh,
+    material: usize,
+    vertices: HashMap<[u64; 3], usize>,
+}
+
+impl Welder {
+    fn new() -> Welder {
+        let mut mesh = Mesh::new();
+        let material = mesh.add_material(DEFAULT_MATERIAL);
+        Welder {
+            mesh,
+            material,
+            vertices: HashMap::new(),
+        }
+    }
+
+    fn add_triangle(&mut self, corners: [Point3; 3]) -> Result<(), MeshError> {
+        let indices = corners.map(|p| {
+            // Adding zero turns -0.0 into 0.0 so both weld together.
+            let key = [
+                (p.x + 0.0).to_bits(),
+                (p.y + 0.0).to_bits(),
+                (p.z + 0.0).to_bits(),
+            ];
+            let mesh = &mut self.mesh;
+            *self
+                .vertices
+                .entry(key)
+                .or_insert_with(|| mesh.add_position(p))
+        });
+
+        // Degenerate triangles can't be hit, no need to keep them.
+        if indices[0] != indices[1] && indices[1] != indices[2] && indices[0] != indices[2] {
+            self.mesh.add_face(Face::new(indices, self.material))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_ascii_stl() {
+    let text = "solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    let mesh = parse_stl(text.as_bytes(), "square.stl", None).unwrap();
+    assert_eq!(mesh.faces().len(), 2);
+    assert_eq!(mesh.positions().len(), 4);
+}
+
+#[test]
+fn parse_binary_stl() {
+    // Binary files may start with "solid" too.
+    let mut bytes = b"solid but binary".to_vec();
+    bytes.resize(80, 0);
+    bytes.extend(2u32.to_le_bytes());
+    for triangle in [
+        [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+    ] {
+        for n in [0.0f32, 0.0, 1.0] {
+            bytes.extend(n.to_le_bytes());
+        }
+        for c in triangle {
+            bytes.extend(c.to_le_bytes());
+        }
+        bytes.extend([0, 0]);
+    }
+
+    let length = bytes.len() as u64;
+    let mesh = parse_stl(bytes.as_slice(), "square.stl", Some(length)).unwrap();
+    assert_eq!(mesh.faces().len(), 2);
+    assert_eq!(mesh.positions().len(), 4);
+    assert_eq!(mesh.positions()[2], Point3::new(1.0, 1.0, 0.0));
+
+    let e = parse_stl(&bytes[..150], "short.stl", None).unwrap_err();
+    assert_eq!(e.to_string(), "short.stl: truncated at triangle 1 of 2");
+}
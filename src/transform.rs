@@ -0,0 +1,534 @@
+use std::ops;
+
+use super::aabb::*;
+use super::vec3::*;
+
+// Row-major 4x4 matrix, points are column vectors on the right.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Matrix4 { m }
+    }
+
+    // From 16 values in column-major order, as glTF stores them.
+    pub fn from_column_major(values: &[f64; 16]) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, value) in values.iter().enumerate() {
+            m[i % 4][i / 4] = *value;
+        }
+        Matrix4 { m }
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.m[column][row];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None if the matrix is
+    // singular.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut a = self.m;
+        let mut inverse = Matrix4::identity().m;
+
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|i, j| f64::abs(a[*i][column]).total_cmp(&f64::abs(a[*j][column])))
+                .unwrap();
+            if f64::abs(a[pivot][column]) < 1e-12 {
+                return None;
+            }
+            a.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let factor = 1.0 / a[column][column];
+            for k in 0..4 {
+                a[column][k] *= factor;
+                inverse[column][k] *= factor;
+            }
+
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = a[row][column];
+                for k in 0..4 {
+                    a[row][k] -= factor * a[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+
+        Some(Matrix4 { m: inverse })
+    }
+}
+
+impl ops::Mul for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[row][k] * other.m[k][column]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+// Affine transform, keeps its inverse around since rays are transformed into
+// object space by the inverse.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    // None if the matrix can't be inverted.
+    pub fn from_matrix(matrix: Matrix4) -> Option<Transform> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn translation(offset: Vec3) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][3] = offset[axis];
+            inverse.m[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    pub fn scaling(factor: Vec3) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for axis in 0..3 {
+            matrix.m[axis][axis] = factor[axis];
+            inverse.m[axis][axis] = 1.0 / factor[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // Counterclockwise rotation by angle radians around axis.
+    pub fn rotation(axis: Vec3, angle: f64) -> Transform {
+        let Vec3 { x, y, z } = axis.unit();
+        let (sin, cos) = f64::sin_cos(angle);
+        let t = 1.0 - cos;
+
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][0] = t * x * x + cos;
+        matrix.m[0][1] = t * x * y - sin * z;
+        matrix.m[0][2] = t * x * z + sin * y;
+        matrix.m[1][0] = t * x * y + sin * z;
+        matrix.m[1][1] = t * y * y + cos;
+        matrix.m[1][2] = t * y * z - sin * x;
+        matrix.m[2][0] = t * x * z - sin * y;
+        matrix.m[2][1] = t * y * z + sin * x;
+        matrix.m[2][2] = t * z * z + cos;
+
+        // Rotations are orthogonal.
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    // Rotation by the unit quaternion [x, y, z, w].
+    pub fn quaternion(q: [f64; 4]) -> Transform {
+        let length = f64::sqrt(q.iter().map(|c| c * c).sum());
+        let [x, y, z, w] = q.map(|c| c / length);
+
+        let mut matrix = Matrix4::identity();
+        matrix.m[0][0] = 1.0 - 2.0 * (y * y + z * z);
+        matrix.m[0][1] = 2.0 * (x * y - z * w);
+        matrix.m[0][2] = 2.0 * (x * z + y * w);
+        matrix.m[1][0] = 2.0 * (x * y + z * w);
+        matrix.m[1][1] = 1.0 - 2.0 * (x * x + z * z);
+        matrix.m[1][2] = 2.0 * (y * z - x * w);
+        matrix.m[2][0] = 2.0 * (x * z - y * w);
+        matrix.m[2][1] = 2.0 * (y * z + x * w);
+        matrix.m[2][2] = 1.0 - 2.0 * (x * x + y * y);
+
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    // Places an object at position with its -z axis facing target and its
+    // +y axis towards up, the way cameras look. None if target is at position
+    // or in the direction of up.
+    pub fn look_at(position: Point3, target: Point3, up: Vec3) -> Option<Transform> {
+        let z = (position - target).unit();
+        let x = up.cross(z);
+        if !z.magnitude2().is_normal() || x.nearly_zero() {
+            return None;
+        }
+        let x = x.unit();
+        let y = z.cross(x);
+
+        let mut matrix = Matrix4::identity();
+        for (column, axis) in [x, y, z, position].iter().enumerate() {
+            for row in 0..3 {
+                matrix.m[row][column] = axis[row];
+            }
+        }
+        Transform::from_matrix(matrix)
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    // Applies self first and then other.
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    // Negative when the transform mirrors, which flips triangle winding.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.matrix.m;
+        let c0 = Vec3::new(m[0][0], m[1][0], m[2][0]);
+        let c1 = Vec3::new(m[0][1], m[1][1], m[2][1]);
+        let c2 = Vec3::new(m[0][2], m[1][2], m[2][2]);
+        c0.dot(c1.cross(c2))
+    }
+
+    #[inline]
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.vector(p)
+            + Vec3::new(
+                self.matrix.m[0][3],
+                self.matrix.m[1][3],
+                self.matrix.m[2][3],
+            )
+    }
+
+    #[inline]
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        multiply(&self.matrix, v)
+    }
+
+    // Normals transform by the inverse transpose, the result is not unit
+    // length.
+    #[inline]
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        multiply(&self.inverse.transpose(), n)
+    }
+
+    pub fn aabb(&self, aabb: &Aabb) -> Aabb {
+        Aabb::from_points(aabb.corners().map(|p| self.point(p)))
+    }
+}
+
+// a * b applies b first, like the matrix product.
+impl ops::Mul for Transform {
+    type Output = Transform;
+    fn mul(self, other: Transform) -> Transform {
+        other.then(&self)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+// A transform as scale, rotation and translation, applied in that order,
+// which can be interpolated. The rotation is a quaternion [x, y, z, w].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Pose {
+    pub scale: Vec3,
+    pub rotation: [f64; 4],
+    pub translation: Vec3,
+}
+
+impl Pose {
+    pub fn identity() -> Pose {
+        Pose {
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            rotation: [0.0, 0.0, 0.0, 1.0],
+            translation: Vec3::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::scaling(self.scale)
+            .then(&Transform::quaternion(self.rotation))
+            .then(&Transform::translation(self.translation))
+    }
+
+    // Straight lines for scale and translation, the rotation turns along the
+    // shortest arc at a constant rate.
+    pub fn interpolate(&self, other: &Pose, t: f64) -> Pose {
+        let (a, mut b) = (normalize(self.rotation), normalize(other.rotation));
+        let mut cos = (0..4).map(|i| a[i] * b[i]).sum::<f64>();
+        if cos < 0.0 {
+            b = b.map(|c| -c);
+            cos = -cos;
+        }
+
+        let (wa, wb) = match cos > 0.9995 {
+            true => (1.0 - t, t),
+            false => {
+                let angle = f64::acos(cos);
+                let sin = f64::sin(angle);
+                (f64::sin((1.0 - t) * angle) / sin, f64::sin(t * angle) / sin)
+            }
+        };
+
+        Pose {
+            scale: self.scale + (other.scale - self.scale) * t,
+            rotation: normalize([0, 1, 2, 3].map(|i| a[i] * wa + b[i] * wb)),
+            translation: self.translation + (other.translation - self.translation) * t,
+        }
+    }
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+// Hamilton product, the rotation of b followed by a.
+pub fn quaternion_product(a: [f64; 4], b: [f64; 4]) -> [f64; 4] {
+    let [ax, ay, az, aw] = a;
+    let [bx, by, bz, bw] = b;
+    [
+        aw * bx + ax * bw + ay * bz - az * by,
+        aw * by - ax * bz + ay * bw + az * bx,
+        aw * bz + ax * by - ay * bx + az * bw,
+        aw * bw - ax * bx - ay * by - az * bz,
+    ]
+}
+
+fn normalize(q: [f64; 4]) -> [f64; 4] {
+    let length = f64::sqrt(q.iter().map(|c| c * c).sum());
+    q.map(|c| c / length)
+}
+
+// Moves from the start pose at time0 to the end pose at time1, holding
+// either pose outside of that interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnimatedTransform {
+    start: Pose,
+    end: Pose,
+    time0: f64,
+    time1: f64,
+}
+
+impl AnimatedTransform {
+    pub fn new(start: Pose, end: Pose, time0: f64, time1: f64) -> AnimatedTransform {
+        AnimatedTransform {
+            start,
+            end,
+            time0,
+            time1,
+        }
+    }
+
+    pub fn start(&self) -> &Pose {
+        &self.start
+    }
+
+    pub fn end(&self) -> &Pose {
+        &self.end
+    }
+
+    pub fn times(&self) -> (f64, f64) {
+        (self.time0, self.time1)
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        if self.time1 <= self.time0 {
+            return self.start.transform();
+        }
+        let t = f64::clamp((time - self.time0) / (self.time1 - self.time0), 0.0, 1.0);
+        self.start.interpolate(&self.end, t).transform()
+    }
+
+    // Box containing aabb during the whole motion. The box is swept through
+    // the poses in small steps, padded by how far a corner can bulge out of
+    // the straight line between two steps while rotating.
+    pub fn aabb(&self, aabb: &Aabb) -> Aabb {
+        const STEPS: usize = 64;
+
+        let mut out = Aabb::empty();
+        for step in 0..=STEPS {
+            let t = step as f64 / STEPS as f64;
+            out = out.union(&self.start.interpolate(&self.end, t).transform().aabb(aabb));
+        }
+
+        let (a, b) = (normalize(self.start.rotation), normalize(self.end.rotation));
+        let cos = (0..4).map(|i| a[i] * b[i]).sum::<f64>().abs();
+        let step_angle = 2.0 * f64::acos(f64::min(cos, 1.0)) / STEPS as f64;
+
+        let scale = [self.start.scale, self.end.scale]
+            .iter()
+            .flat_map(|s| [s.x.abs(), s.y.abs(), s.z.abs()])
+            .fold(0.0, f64::max);
+        let radius = aabb
+            .corners()
+            .iter()
+            .map(|c| c.magnitude() * scale)
+            .fold(0.0, f64::max);
+
+        out.pad(radius * (1.0 - f64::cos(step_angle / 2.0)))
+    }
+}
+
+#[inline]
+fn multiply(matrix: &Matrix4, v: Vec3) -> Vec3 {
+    let m = &matrix.m;
+    Vec3::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+    )
+}
+
+#[test]
+fn compose_transforms() {
+    let transform = Transform::scaling(Vec3::new(2.0, 2.0, 2.0))
+        .then(&Transform::rotation(Vec3::new(0.0, 0.0, 1.0), PI / 2.0))
+        .then(&Transform::translation(Vec3::new(1.0, 0.0, 0.0)));
+
+    let p = transform.point(Point3::new(1.0, 0.0, 0.0));
+    assert!(p.nearly_eq(Point3::new(1.0, 2.0, 0.0)));
+    assert!(transform
+        .inverse()
+        .point(p)
+        .nearly_eq(Point3::new(1.0, 0.0, 0.0)));
+
+    // Vectors ignore the translation.
+    let v = transform.vector(Vec3::new(0.0, 1.0, 0.0));
+    assert!(v.nearly_eq(Vec3::new(-2.0, 0.0, 0.0)));
+
+    // Normals stay perpendicular to the surface under non-uniform scaling.
+    let squash = Transform::scaling(Vec3::new(1.0, 4.0, 1.0));
+    let tangent = squash.vector(Vec3::new(1.0, -1.0, 0.0));
+    let normal = squash.normal(Vec3::new(1.0, 1.0, 0.0));
+    assert_eq!(tangent.dot(normal), 0.0);
+}
+
+#[test]
+fn invert_transforms() {
+    let transform = Transform::translation(Vec3::new(1.0, 2.0, 3.0))
+        * Transform::quaternion([0.0, f64::sin(0.3), 0.0, f64::cos(0.3)])
+        * Transform::scaling(Vec3::new(1.0, 2.0, -3.0));
+    assert!(transform.determinant() < 0.0);
+
+    let general = Transform::from_matrix(*transform.matrix()).unwrap();
+    let p = Point3::new(0.5, -1.0, 2.0);
+    assert!(general.inverse().point(transform.point(p)).nearly_eq(p));
+
+    let rotation = Transform::quaternion([0.0, f64::sin(0.3), 0.0, f64::cos(0.3)]);
+    let axis_angle = Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 0.6);
+    assert!(rotation
+        .vector(Vec3::new(1.0, 0.0, 0.0))
+        .nearly_eq(axis_angle.vector(Vec3::new(1.0, 0.0, 0.0))));
+
+    let mut singular = Matrix4::identity();
+    singular.m[1][1] = 0.0;
+    assert!(Transform::from_matrix(singular).is_none());
+
+    let column_major = Matrix4::from_column_major(&[
+        1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 4.0, 5.0, 6.0, 1.0,
+    ]);
+    assert_eq!(
+        column_major,
+        *Transform::translation(Vec3::new(4.0, 5.0, 6.0)).matrix()
+    );
+}
+
+#[test]
+fn look_at_transform() {
+    let position = Point3::new(0.0, 0.0, 5.0);
+    let transform = Transform::look_at(
+        position,
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    )
+    .unwrap();
+    assert!(transform
+        .vector(Vec3::new(0.0, 0.0, -1.0))
+        .nearly_eq(Vec3::new(0.0, 0.0, -1.0)));
+    assert!(transform
+        .point(Point3::new(0.0, 0.0, 0.0))
+        .nearly_eq(position));
+
+    let looking_up = Transform::look_at(
+        position,
+        Point3::new(0.0, 9.0, 5.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    );
+    assert!(looking_up.is_none());
+}
+
+#[test]
+fn animate_transform() {
+    let quarter_turn = [0.0, f64::sin(PI / 4.0), 0.0, f64::cos(PI / 4.0)];
+    let end = Pose {
+        scale: Vec3::new(3.0, 3.0, 3.0),
+        rotation: quarter_turn,
+        translation: Vec3::new(0.0, 4.0, 0.0),
+    };
+    let animated = AnimatedTransform::new(Pose::identity(), end, 0.0, 2.0);
+
+    let p = Point3::new(1.0, 0.0, 0.0);
+    assert!(animated.at(-1.0).point(p).nearly_eq(p));
+    assert!(animated
+        .at(2.0)
+        .point(p)
+        .nearly_eq(Point3::new(0.0, 4.0, -3.0)));
+
+    // Halfway the rotation is an eighth of a turn.
+    let halfway = animated.at(1.0).point(p);
+    let expected = Point3::new(f64::cos(PI / 4.0), 0.0, -f64::sin(PI / 4.0)) * 2.0;
+    assert!(halfway.nearly_eq(expected + Vec3::new(0.0, 2.0, 0.0)));
+
+    let unit = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+    let swept = animated.aabb(&unit);
+    for step in 0..=100 {
+        let transform = animated.at(step as f64 / 50.0);
+        for corner in unit.corners() {
+            let q = transform.point(corner);
+            assert!(swept.union(&Aabb::new(q, q)) == swept);
+        }
+    }
+}
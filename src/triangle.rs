@@ -0,0 +1,136 @@
+use super::aabb::*;
+use super::hittable::*;
+use super::material::*;
+use super::ray::*;
+use super::vec3::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Triangle {
+    vertices: [Point3; 3],
+    material: Material,
+    normal: Vec3,
+}
+
+impl Triangle {
+    // Vertices in counter-clockwise order, seen from the front.
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Material) -> Triangle {
+        let normal = (b - a).cross(c - a).unit();
+
+        Triangle {
+            vertices: [a, b, c],
+            material,
+            normal,
+        }
+    }
+
+    pub fn vertices(&self) -> [Point3; 3] {
+        self.vertices
+    }
+
+    pub fn material(&self) -> Material {
+        self.material
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+        let [a, b, c] = self.vertices;
+        let (solution, u, v) = intersect_triangle(ray, a, b, c, minimum, maximum)?;
+
+        let mut hit = Hit::new(
+            ray.at(solution),
+            self.normal,
+            solution,
+            ray.direction.dot(self.normal) < 0.0,
+            self.material,
+        );
+        hit.uv = (u, v);
+
+        if !hit.front {
+            hit.normal = -hit.normal;
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(self.vertices))
+    }
+}
+
+// Möller–Trumbore ray/triangle intersection, returns the ray solution and the
+// barycentric coordinates of b and c.
+pub fn intersect_triangle(
+    ray: &Ray,
+    a: Point3,
+    b: Point3,
+    c: Point3,
+    minimum: f64,
+    maximum: f64,
+) -> Option<(f64, f64, f64)> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+
+    let p = ray.direction.cross(edge_2);
+    let determinant = edge_1.dot(p);
+    if f64::abs(determinant) < EPSILON {
+        return None;
+    }
+
+    let determinant_reciprocol = 1.0 / determinant;
+    let s = ray.origin - a;
+
+    let u = s.dot(p) * determinant_reciprocol;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge_1);
+    let v = ray.direction.dot(q) * determinant_reciprocol;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let solution = edge_2.dot(q) * determinant_reciprocol;
+    if solution < minimum || solution > maximum {
+        return None;
+    }
+
+    Some((solution, u, v))
+}
+
+#[test]
+fn hit_triangle() {
+    let triangle = Triangle::new(
+        Point3::new(-1.0, -1.0, 0.0),
+        Point3::new(1.0, -1.0, 0.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Material::Diffuse(Color::new(0.5, 0.5, 0.5)),
+    );
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    let hit = triangle.hit(&ray, 0.0, f64::INFINITY);
+
+    assert!(hit.is_some());
+    let hit = hit.unwrap();
+
+    assert!(hit.front);
+    assert_eq!(hit.solution, 1.0);
+    assert_eq!(hit.point, Point3::new(0.0, 0.0, 0.0));
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 0.0, 1.0));
+    let hit = triangle.hit(&ray, 0.0, f64::INFINITY);
+
+    assert!(hit.is_some());
+    let hit = hit.unwrap();
+
+    assert!(!hit.front);
+    assert_eq!(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+
+    let ray = Ray::new(Point3::new(1.0, 1.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+    assert!(triangle.hit(&ray, 0.0, f64::INFINITY).is_none());
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
+    assert!(triangle.hit(&ray, 0.0, f64::INFINITY).is_none());
+}
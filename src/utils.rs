@@ -21,11 +21,12 @@ pub fn write_image(filename: &str, bytes: &[u8], width: usize, height: usize) {
     let encoder = PngEncoder::new(file);
 
     encoder
-        .write_image(&bytes, width as u32, height as u32, ColorType::Rgb8)
+        .write_image(bytes, width as u32, height as u32, ColorType::Rgb8)
         .expect("Failed to write image");
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn test_parse_pair() {
     assert_eq!(parse_pair::<f32>("", ','), None);
     assert_eq!(parse_pair::<f32>("1.2", ','), None);
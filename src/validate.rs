@@ -0,0 +1,1416 @@
+use std::fmt;
+
+use super::vec3::*;
+
+// Scene file validation. Unlike deserializing with serde, which stops at the
+// first problem, this walks the whole document and reports every problem it
+// finds together with its JSON path and line/column.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}: {}: {}",
+                self.line, self.column, self.path, self.message
+            )
+        }
+    }
+}
+
+pub fn validate_scene(text: &str) -> Vec<Diagnostic> {
+    let root = match Parser::new(text).parse_document() {
+        Ok(root) => root,
+        Err(diagnostic) => return vec![diagnostic],
+    };
+
+    let mut validator = Validator {
+        diagnostics: Vec::new(),
+    };
+    validator.check_finite(&root, &mut String::new());
+    validator.scene(&root);
+
+    let mut diagnostics = validator.diagnostics;
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+// A JSON document that remembers where each value came from.
+
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+}
+
+#[derive(Debug)]
+struct Node {
+    value: Json,
+    line: usize,
+    column: usize,
+}
+
+impl Node {
+    fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self.value {
+            Json::Null => "null",
+            Json::Bool(_) => "a boolean",
+            Json::Number(_) => "a number",
+            Json::String(_) => "a string",
+            Json::Array(_) => "an array",
+            Json::Object(_) => "an object",
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Parser<'a> {
+        Parser {
+            text,
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Node, Diagnostic> {
+        let node = self.parse_value()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(node),
+            Some(_) => Err(self.error("unexpected characters after the end of the document")),
+        }
+    }
+
+    fn error(&self, message: &str) -> Diagnostic {
+        Diagnostic {
+            path: String::new(),
+            line: self.line,
+            column: self.column,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.offset..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    // Only the whitespace JSON allows, others are syntax errors.
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !matches!(c, ' ' | '\t' | '\r' | '\n') {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Diagnostic> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(&format!("expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(&format!("expected `{}`, found end of file", expected))),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Node, Diagnostic> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+
+        let value = match self.peek() {
+            None => return Err(self.error("unexpected end of file")),
+            Some('{') => self.parse_object()?,
+            Some('[') => self.parse_array()?,
+            Some('"') => Json::String(self.parse_string()?),
+            Some(c) if c == '-' || c.is_ascii_digit() || c.is_ascii_alphabetic() => {
+                self.parse_literal()?
+            }
+            Some(c) => return Err(self.error(&format!("unexpected character `{}`", c))),
+        };
+
+        Ok(Node {
+            value,
+            line,
+            column,
+        })
+    }
+
+    fn parse_object(&mut self) -> Result<Json, Diagnostic> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Json::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a quoted member name"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            members.push((key, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some('}') => {
+                    self.bump();
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("expected `,` or `}` in object")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, Diagnostic> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {
+                    self.bump();
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("expected `,` or `]` in array")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, Diagnostic> {
+        self.expect('"')?;
+        let mut out = String::new();
+
+        loop {
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let start = self.offset;
+                        for _ in 0..4 {
+                            self.bump();
+                        }
+                        let code = self
+                            .text
+                            .get(start..self.offset)
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        out.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    _ => return Err(self.error("invalid escape sequence")),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    // Numbers and the bare words true/false/null. NaN and Infinity are not
+    // JSON, but they are accepted here so they can be reported as non-finite
+    // numbers rather than as a syntax error.
+    fn parse_literal(&mut self) -> Result<Json, Diagnostic> {
+        let (line, column) = (self.line, self.column);
+        let start = self.offset;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '-' || c == '+' || c == '.') {
+                break;
+            }
+            self.bump();
+        }
+
+        let word = &self.text[start..self.offset];
+        let value = match word {
+            "true" => Json::Bool(true),
+            "false" => Json::Bool(false),
+            "null" => Json::Null,
+            "NaN" | "-NaN" => Json::Number(f64::NAN),
+            "Infinity" => Json::Number(f64::INFINITY),
+            "-Infinity" => Json::Number(f64::NEG_INFINITY),
+            _ => match word.parse::<f64>() {
+                Ok(number) if is_json_number(word) => Json::Number(number),
+                _ => {
+                    return Err(Diagnostic {
+                        path: String::new(),
+                        line,
+                        column,
+                        message: format!("invalid literal `{}`", word),
+                    })
+                }
+            },
+        };
+
+        Ok(value)
+    }
+}
+
+const MATERIAL_TAGS: &[&str] = &["diffused", "metalic", "dielectric", "emissive"];
+const LIGHT_TAGS: &[&str] = &["point", "spot", "directional"];
+const CAMERA_PROJECTIONS: &[&str] = &["perspective", "orthographic", "fisheye", "equirectangular"];
+const CAMERA_VIEWS: &[&str] = &[
+    "focal_length",
+    "vertical_fov",
+    "horizontal_fov",
+    "sensor",
+    "view_height",
+];
+const FISHEYE_MAPPINGS: &[&str] = &["equidistant", "equisolid"];
+const HITTABLE_TAGS: &[&str] = &[
+    "sphere",
+    "mesh",
+    "quad",
+    "box",
+    "plane",
+    "disk",
+    "cylinder",
+    "cone",
+    "heightfield",
+    "csg",
+];
+const CSG_OPERATIONS: &[&str] = &["union", "intersection", "difference"];
+
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn report(&mut self, path: &str, node: &Node, message: String) {
+        self.diagnostics.push(Diagnostic {
+            path: path.to_string(),
+            line: node.line,
+            column: node.column,
+            message,
+        });
+    }
+
+    fn check_finite(&mut self, node: &Node, path: &mut String) {
+        match &node.value {
+            Json::Number(n) if !n.is_finite() => {
+                let path = path.clone();
+                self.report(&path, node, format!("number is not finite ({})", n));
+            }
+            Json::Array(elements) => {
+                for (index, element) in elements.iter().enumerate() {
+                    let length = path.len();
+                    path.push_str(&format!("[{}]", index));
+                    self.check_finite(element, path);
+                    path.truncate(length);
+                }
+            }
+            Json::Object(members) => {
+                for (key, member) in members.iter() {
+                    let length = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                    self.check_finite(member, path);
+                    path.truncate(length);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn object<'n>(&mut self, path: &str, node: &'n Node) -> Option<&'n Node> {
+        match node.value {
+            Json::Object(_) => Some(node),
+            _ => {
+                self.report(
+                    path,
+                    node,
+                    format!("expected an object, found {}", node.type_name()),
+                );
+                None
+            }
+        }
+    }
+
+    fn field<'n>(&mut self, path: &str, node: &'n Node, key: &str) -> Option<&'n Node> {
+        let field = node.get(key);
+        if field.is_none() {
+            self.report(path, node, format!("missing field `{}`", key));
+        }
+        field
+    }
+
+    fn number(&mut self, path: &str, node: &Node) -> Option<f64> {
+        match node.value {
+            Json::Number(n) if n.is_finite() => Some(n),
+            // Already reported by check_finite.
+            Json::Number(_) => None,
+            _ => {
+                self.report(
+                    path,
+                    node,
+                    format!("expected a number, found {}", node.type_name()),
+                );
+                None
+            }
+        }
+    }
+
+    fn number_field(&mut self, path: &str, node: &Node, key: &str) -> Option<f64> {
+        let field = self.field(path, node, key)?;
+        self.number(&join(path, key), field)
+    }
+
+    fn positive_field(&mut self, path: &str, node: &Node, key: &str) {
+        let field = match self.field(path, node, key) {
+            Some(field) => field,
+            None => return,
+        };
+        let path = join(path, key);
+        if let Some(n) = self.number(&path, field) {
+            if n <= 0.0 {
+                self.report(&path, field, format!("must be positive, found {}", n));
+            }
+        }
+    }
+
+    fn string_field(&mut self, path: &str, node: &Node, key: &str) {
+        if let Some(field) = self.field(path, node, key) {
+            if !matches!(field.value, Json::String(_)) {
+                self.report(
+                    &join(path, key),
+                    field,
+                    format!("expected a string, found {}", field.type_name()),
+                );
+            }
+        }
+    }
+
+    fn integer(&mut self, path: &str, node: &Node) -> Option<f64> {
+        let n = self.number(path, node)?;
+        if n.fract() != 0.0 {
+            self.report(path, node, format!("expected an integer, found {}", n));
+            return None;
+        }
+        Some(n)
+    }
+
+    fn count_field(&mut self, path: &str, node: &Node, key: &str) -> Option<f64> {
+        let field = self.field(path, node, key)?;
+        let path = join(path, key);
+        let n = self.integer(&path, field)?;
+        if n < 0.0 {
+            self.report(&path, field, format!("must not be negative, found {}", n));
+            return None;
+        }
+        if n == 0.0 {
+            self.report(&path, field, "must be at least 1".to_string());
+            return None;
+        }
+        Some(n)
+    }
+
+    fn vector(&mut self, path: &str, node: &Node) -> Option<Vec3> {
+        self.object(path, node)?;
+        let x = self.number_field(path, node, "x");
+        let y = self.number_field(path, node, "y");
+        let z = self.number_field(path, node, "z");
+        Some(Vec3::new(x?, y?, z?))
+    }
+
+    fn color(&mut self, path: &str, node: &Node) {
+        if self.object(path, node).is_some() {
+            self.number_field(path, node, "r");
+            self.number_field(path, node, "g");
+            self.number_field(path, node, "b");
+        }
+    }
+
+    fn tag<'n>(
+        &mut self,
+        path: &str,
+        node: &'n Node,
+        key: &str,
+        known: &[&str],
+    ) -> Option<&'n str> {
+        let field = self.field(path, node, key)?;
+        let path = join(path, key);
+        match &field.value {
+            Json::String(tag) if known.contains(&tag.as_str()) => Some(tag),
+            Json::String(tag) => {
+                self.report(
+                    &path,
+                    field,
+                    format!(
+                        "unknown {} `{}`, expected one of: {}",
+                        key,
+                        tag,
+                        known.join(", ")
+                    ),
+                );
+                None
+            }
+            _ => {
+                self.report(
+                    &path,
+                    field,
+                    format!("expected a string, found {}", field.type_name()),
+                );
+                None
+            }
+        }
+    }
+
+    fn elements<'n>(&mut self, path: &str, node: &'n Node) -> &'n [Node] {
+        match &node.value {
+            Json::Array(elements) => elements,
+            _ => {
+                self.report(
+                    path,
+                    node,
+                    format!("expected an array, found {}", node.type_name()),
+                );
+                &[]
+            }
+        }
+    }
+
+    fn scene(&mut self, root: &Node) {
+        if self.object("", root).is_none() {
+            return;
+        }
+
+        if let Some(image) = self.field("", root, "image") {
+            self.image("image", image);
+        }
+
+        let mut material_count = None;
+        if let Some(materials) = self.field("", root, "materials") {
+            let materials = self.elements("materials", materials);
+            for (index, material) in materials.iter().enumerate() {
+                self.material(&format!("materials[{}]", index), material);
+            }
+            material_count = Some(materials.len());
+        }
+
+        if let Some(camera) = self.field("", root, "camera") {
+            self.camera("camera", camera);
+        }
+
+        if let Some(background) = root.get("background") {
+            self.color("background", background);
+        }
+
+        if let Some(world) = self.field("", root, "world") {
+            for (index, hittable) in self.elements("world", world).iter().enumerate() {
+                self.hittable(&format!("world[{}]", index), hittable, material_count);
+            }
+        }
+
+        if let Some(lights) = root.get("lights") {
+            for (index, light) in self.elements("lights", lights).iter().enumerate() {
+                self.light(&format!("lights[{}]", index), light);
+            }
+        }
+    }
+
+    fn light(&mut self, path: &str, node: &Node) {
+        if self.object(path, node).is_none() {
+            return;
+        }
+
+        let tag = self.tag(path, node, "light", LIGHT_TAGS);
+        if matches!(tag, Some("point" | "spot")) {
+            if let Some(position) = self.field(path, node, "position") {
+                self.vector(&join(path, "position"), position);
+            }
+        }
+        if matches!(tag, Some("spot" | "directional")) {
+            if let Some(direction) = self.field(path, node, "direction") {
+                let path = join(path, "direction");
+                if self
+                    .vector(&path, direction)
+                    .is_some_and(|d| d.nearly_zero())
+                {
+                    self.report(&path, direction, String::from("must not be zero"));
+                }
+            }
+        }
+        if tag.is_none() {
+            return;
+        }
+
+        if let Some(color) = self.field(path, node, "color") {
+            self.color(&join(path, "color"), color);
+        }
+        self.range_field(path, node, "intensity", 0.0..=f64::INFINITY, true);
+        if tag == Some("spot") {
+            self.range_field(path, node, "cone_angle", 0.0..=180.0, true);
+            self.range_field(path, node, "softness", 0.0..=1.0, false);
+        }
+        if tag == Some("directional") {
+            self.range_field(path, node, "angular_diameter", 0.0..=180.0, false);
+        }
+    }
+
+    // A number within range, which required fields must have.
+    fn range_field(
+        &mut self,
+        path: &str,
+        node: &Node,
+        key: &str,
+        range: std::ops::RangeInclusive<f64>,
+        required: bool,
+    ) {
+        if !required && node.get(key).is_none() {
+            return;
+        }
+        let field = match self.field(path, node, key) {
+            Some(field) => field,
+            None => return,
+        };
+        let path = join(path, key);
+        if let Some(n) = self.number(&path, field) {
+            if !range.contains(&n) {
+                let message = match range.end().is_infinite() {
+                    true => format!("must not be below {}, found {}", range.start(), n),
+                    false => format!(
+                        "must be between {} and {}, found {}",
+                        range.start(),
+                        range.end(),
+                        n
+                    ),
+                };
+                self.report(&path, field, message);
+            }
+        }
+    }
+
+    fn image(&mut self, path: &str, node: &Node) {
+        if self.object(path, node).is_none() {
+            return;
+        }
+        self.count_field(path, node, "width");
+        self.count_field(path, node, "height");
+        self.count_field(path, node, "samples_per_pixel");
+    }
+
+    fn material(&mut self, path: &str, node: &Node) {
+        if self.object(path, node).is_none() {
+            return;
+        }
+
+        match self.tag(path, node, "material", MATERIAL_TAGS) {
+            Some("diffused") => {
+                if let Some(color) = self.field(path, node, "color") {
+                    self.color(&join(path, "color"), color);
+                }
+            }
+            Some("metalic") => {
+                if let Some(color) = self.field(path, node, "color") {
+                    self.color(&join(path, "color"), color);
+                }
+                self.number_field(path, node, "fuzziness");
+            }
+            Some("dielectric") => {
+                self.positive_field(path, node, "refraction_index");
+                self.range_field(path, node, "roughness", 0.0..=1.0, false);
+                if let Some(absorption) = node.get("absorption") {
+                    let path = join(path, "absorption");
+                    if self.object(&path, absorption).is_some() {
+                        if let Some(color) = self.field(&path, absorption, "color") {
+                            let path = join(&path, "color");
+                            if self.object(&path, color).is_some() {
+                                for channel in ["r", "g", "b"] {
+                                    self.range_field(&path, color, channel, 0.0..=1.0, true);
+                                }
+                            }
+                        }
+                        self.positive_field(&path, absorption, "distance");
+                    }
+                }
+            }
+            Some("emissive") => {
+                if let Some(color) = self.field(path, node, "color") {
+                    self.color(&join(path, "color"), color);
+                }
+                if let Some(intensity) = self.number_field(path, node, "intensity") {
+                    if intensity < 0.0 {
+                        let field = node.get("intensity").unwrap();
+                        self.report(
+                            &join(path, "intensity"),
+                            field,
+                            format!("must not be negative, found {}", intensity),
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // Exactly one of the fields the projection takes sets how much is in view.
+    fn camera_view(&mut self, path: &str, node: &Node, projection: &str) {
+        let takes: &[&str] = match projection {
+            "perspective" => &["focal_length", "vertical_fov", "horizontal_fov", "sensor"],
+            "orthographic" => &["view_height"],
+            "fisheye" => &["vertical_fov", "horizontal_fov"],
+            _ => &[],
+        };
+
+        let mut taken: Vec<&str> = Vec::new();
+        for key in CAMERA_VIEWS {
+            let field = match node.get(key) {
+                Some(field) => field,
+                None => continue,
+            };
+            if !takes.contains(key) {
+                self.report(
+                    &join(path, key),
+                    field,
+                    format!("not used by {} cameras", projection),
+                );
+            } else if let Some(first) = taken.first() {
+                self.report(
+                    &join(path, key),
+                    field,
+                    format!("conflicts with `{}`, only one of them can be set", first),
+                );
+            } else {
+                taken.push(key);
+            }
+        }
+        if taken.is_empty() && !takes.is_empty() {
+            let keys: Vec<String> = takes.iter().map(|key| format!("`{}`", key)).collect();
+            self.report(path, node, format!("missing one of {}", keys.join(", ")));
+        }
+
+        for key in ["focal_length", "view_height"] {
+            if node.get(key).is_some() {
+                self.positive_field(path, node, key);
+            }
+        }
+        // Fisheyes can see all around.
+        let most = match projection {
+            "fisheye" => 360.0,
+            _ => 180.0,
+        };
+        for key in ["vertical_fov", "horizontal_fov"] {
+            let field = match node.get(key) {
+                Some(field) => field,
+                None => continue,
+            };
+            let path = join(path, key);
+            if let Some(fov) = self.number(&path, field) {
+                let valid = match projection {
+                    "fisheye" => fov > 0.0 && fov <= most,
+                    _ => fov > 0.0 && fov < most,
+                };
+                if !valid {
+                    self.report(
+                        &path,
+                        field,
+                        format!("must be between 0 and {} degrees, found {}", most, fov),
+                    );
+                }
+            }
+        }
+        if let Some(sensor) = node.get("sensor") {
+            let path = join(path, "sensor");
+            if self.object(&path, sensor).is_some() {
+                self.positive_field(&path, sensor, "width");
+                self.positive_field(&path, sensor, "focal_length");
+            }
+        }
+        if let Some(mapping) = node.get("mapping") {
+            match projection {
+                "fisheye" => {
+                    self.tag(path, node, "mapping", FISHEYE_MAPPINGS);
+                }
+                _ => self.report(
+                    &join(path, "mapping"),
+                    mapping,
+                    format!("not used by {} cameras", projection),
+                ),
+            }
+        }
+    }
+
+    fn camera(&mut self, path: &str, node: &Node) {
+        if self.object(path, node).is_none() {
+            return;
+        }
+
+        let position = self
+            .field(path, node, "position")
+            .and_then(|p| self.vector(&join(path, "position"), p));
+        let look_at = self.field(path, node, "look_at");
+        let look_at_vector = look_at.and_then(|l| self.vector(&join(path, "look_at"), l));
+        let world_up = match node.get("world_up") {
+            Some(w) => self.vector(&join(path, "world_up"), w),
+            None => Some(Vec3::new(0.0, 1.0, 0.0)),
+        };
+
+        let projection = match node.get("projection") {
+            Some(_) => self.tag(path, node, "projection", CAMERA_PROJECTIONS),
+            None => Some("perspective"),
+        };
+        if let Some(projection) = projection {
+            self.camera_view(path, node, projection);
+        }
+
+        if let (Some(position), Some(look_at_vector), Some(look_at)) =
+            (position, look_at_vector, look_at)
+        {
+            let front = look_at_vector - position;
+            if front.nearly_zero() {
+                self.report(
+                    &join(path, "look_at"),
+                    look_at,
+                    "look_at is the same point as position, the view direction is undefined"
+                        .to_string(),
+                );
+            } else if let Some(world_up) = world_up {
+                if world_up.nearly_zero() {
+                    let at = node.get("world_up").unwrap();
+                    self.report(
+                        &join(path, "world_up"),
+                        at,
+                        "world_up must not be zero".to_string(),
+                    );
+                } else if front.unit().cross(world_up.unit()).nearly_zero() {
+                    let (at, at_path) = match node.get("world_up") {
+                        Some(w) => (w, join(path, "world_up")),
+                        None => (look_at, join(path, "look_at")),
+                    };
+                    self.report(
+                        &at_path,
+                        at,
+                        format!(
+                            "world_up ({}, {}, {}) is parallel to the view direction",
+                            world_up.x, world_up.y, world_up.z
+                        ),
+                    );
+                }
+            }
+        }
+
+        if let Some(shutter) = node.get("shutter") {
+            let path = join(path, "shutter");
+            if self.object(&path, shutter).is_some() {
+                let open = self.number_field(&path, shutter, "open");
+                let close = self.number_field(&path, shutter, "close");
+                if let (Some(open), Some(close)) = (open, close) {
+                    if close < open {
+                        self.report(
+                            &join(&path, "close"),
+                            shutter.get("close").unwrap(),
+                            format!("must not be before open, found {}", close),
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(lens) = node.get("lens") {
+            let path = join(path, "lens");
+            if projection.is_some_and(|p| p != "perspective") {
+                self.report(
+                    &path,
+                    lens,
+                    "only perspective cameras have a lens".to_string(),
+                );
+            }
+            if self.object(&path, lens).is_some() {
+                if let Some(aperture) = self.number_field(&path, lens, "aperture") {
+                    if aperture < 0.0 {
+                        self.report(
+                            &join(&path, "aperture"),
+                            lens.get("aperture").unwrap(),
+                            format!("must not be negative, found {}", aperture),
+                        );
+                    }
+                }
+                if lens.get("focus_distance").is_some() {
+                    self.positive_field(&path, lens, "focus_distance");
+                }
+                if let Some(blades) = lens.get("blades") {
+                    let blades_path = join(&path, "blades");
+                    if let Some(n) = self.integer(&blades_path, blades) {
+                        if n != 0.0 && n < 3.0 {
+                            self.report(
+                                &blades_path,
+                                blades,
+                                format!("must be 0 for round or at least 3, found {}", n),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn hittable(&mut self, path: &str, node: &Node, material_count: Option<usize>) {
+        if self.object(path, node).is_none() {
+            return;
+        }
+
+        match self.tag(path, node, "hittable", HITTABLE_TAGS) {
+            Some("sphere") => {
+                if let Some(position) = self.field(path, node, "position") {
+                    self.vector(&join(path, "position"), position);
+                }
+                self.number_field(path, node, "radius");
+                if let Some(end_position) = node.get("end_position") {
+                    self.vector(&join(path, "end_position"), end_position);
+                }
+                self.material_index(path, node, material_count);
+                self.transform(path, node);
+            }
+            Some("mesh") => {
+                self.string_field(path, node, "file");
+                if node.get("material").is_some() {
+                    self.material_index(path, node, material_count);
+                }
+                self.transform(path, node);
+            }
+            Some("quad") => {
+                for key in ["corner", "u", "v"] {
+                    if let Some(field) = self.field(path, node, key) {
+                        self.vector(&join(path, key), field);
+                    }
+                }
+                self.material_index(path, node, material_count);
+                self.transform(path, node);
+            }
+            Some("box") => {
+                for key in ["minimum", "maximum"] {
+                    if let Some(field) = self.field(path, node, key) {
+                        self.vector(&join(path, key), field);
+                    }
+                }
+                self.material_index(path, node, material_count);
+                self.transform(path, node);
+            }
+            Some(tag @ ("plane" | "disk")) => {
+                if let Some(position) = self.field(path, node, "position") {
+                    self.vector(&join(path, "position"), position);
+                }
+                if let Some(normal) = self.field(path, node, "normal") {
+                    let path = join(path, "normal");
+                    if self.vector(&path, normal).is_some_and(|n| n.nearly_zero()) {
+                        self.report(&path, normal, String::from("must not be zero"));
+                    }
+                }
+                if tag == "disk" {
+                    self.positive_field(path, node, "radius");
+                }
+                self.material_index(path, node, material_count);
+                self.transform(path, node);
+            }
+            Some("cylinder" | "cone") => {
+                if let Some(position) = self.field(path, node, "position") {
+                    self.vector(&join(path, "position"), position);
+                }
+                self.positive_field(path, node, "radius");
+                self.positive_field(path, node, "height");
+                if let Some(capped) = node.get("capped") {
+                    if !matches!(capped.value, Json::Bool(_)) {
+                        self.report(
+                            &join(path, "capped"),
+                            capped,
+                            format!("expected a boolean, found {}", capped.type_name()),
+                        );
+                    }
+                }
+                self.material_index(path, node, material_count);
+                self.transform(path, node);
+            }
+            Some("heightfield") => {
+                self.string_field(path, node, "file");
+                if let Some(position) = self.field(path, node, "position") {
+                    self.vector(&join(path, "position"), position);
+                }
+                if let Some(size) = self.field(path, node, "size") {
+                    let path = join(path, "size");
+                    for key in ["x", "y", "z"] {
+                        self.positive_field(&path, size, key);
+                    }
+                }
+                if node.get("columns").is_some() {
+                    self.count_field(path, node, "columns");
+                }
+                self.material_index(path, node, material_count);
+                self.transform(path, node);
+            }
+            Some("csg") => {
+                self.tag(path, node, "operation", CSG_OPERATIONS);
+                for key in ["left", "right"] {
+                    if let Some(operand) = self.field(path, node, key) {
+                        let path = join(path, key);
+                        self.hittable(&path, operand, material_count);
+                        if let Some(message) = open_operand(operand) {
+                            self.report(&path, operand, message);
+                        }
+                    }
+                }
+                self.transform(path, node);
+            }
+            _ => self.material_index(path, node, material_count),
+        }
+    }
+
+    // translate, rotate, scale and motion are all optional. motion holds
+    // another transform, without motion of its own.
+    fn transform(&mut self, path: &str, node: &Node) {
+        for key in ["translate", "rotate"] {
+            if let Some(field) = node.get(key) {
+                self.vector(&join(path, key), field);
+            }
+        }
+
+        if let Some(field) = node.get("scale") {
+            let path = join(path, "scale");
+            if let Some(scale) = self.vector(&path, field) {
+                if scale.x == 0.0 || scale.y == 0.0 || scale.z == 0.0 {
+                    self.report(&path, field, String::from("must not be zero on any axis"));
+                }
+            }
+        }
+
+        if let Some(motion) = node.get("motion") {
+            let path = join(path, "motion");
+            if self.object(&path, motion).is_some() {
+                self.transform(&path, motion);
+                if let Some(nested) = motion.get("motion") {
+                    self.report(
+                        &join(&path, "motion"),
+                        nested,
+                        String::from("motion can't move again"),
+                    );
+                }
+            }
+        }
+    }
+
+    fn material_index(&mut self, path: &str, node: &Node, material_count: Option<usize>) {
+        let field = match self.field(path, node, "material") {
+            Some(field) => field,
+            None => return,
+        };
+        let path = join(path, "material");
+
+        if let Some(index) = self.integer(&path, field) {
+            if index < 0.0 {
+                self.report(
+                    &path,
+                    field,
+                    format!("must not be negative, found {}", index),
+                );
+            } else if let Some(count) = material_count {
+                if index as usize >= count {
+                    self.report(
+                        &path,
+                        field,
+                        format!(
+                            "material index {} is out of range, there are {} materials",
+                            index, count
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// Why a CSG operand has no inside, None if it is closed or can't be told.
+fn open_operand(node: &Node) -> Option<String> {
+    let tag = match &node.get("hittable")?.value {
+        Json::String(tag) => tag.as_str(),
+        _ => return None,
+    };
+    let capped = !matches!(
+        node.get("capped").map(|c| &c.value),
+        Some(Json::Bool(false))
+    );
+
+    match tag {
+        "quad" | "plane" | "disk" | "mesh" | "heightfield" => {
+            Some(format!("a {} is not a closed object", tag))
+        }
+        "cylinder" | "cone" if !capped => {
+            Some(format!("an uncapped {} is not a closed object", tag))
+        }
+        "sphere" => match node.get("radius")?.value {
+            Json::Number(radius) if radius < 0.0 => Some(String::from(
+                "a sphere with a negative radius is not a closed object",
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+// -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?, which is stricter than Rust's
+// float syntax: no leading zeros, no leading + and digits on both sides of
+// the point.
+fn is_json_number(word: &str) -> bool {
+    let digits =
+        |text: &str| text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let rest = word.strip_prefix('-').unwrap_or(word);
+    let integer = digits(rest);
+    if integer == 0 || (integer > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[integer..];
+
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let count = digits(fraction);
+        if count == 0 {
+            return false;
+        }
+        rest = &fraction[count..];
+    }
+
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let count = digits(exponent);
+        if count == 0 {
+            return false;
+        }
+        rest = &exponent[count..];
+    }
+
+    rest.is_empty()
+}
+
+#[test]
+fn validate_test_scene() {
+    assert!(validate_scene(include_str!("../data/test.json")).is_empty());
+    assert!(validate_scene(include_str!("../data/cornell.json")).is_empty());
+}
+
+#[test]
+fn validate_reports_every_problem() {
+    let text = r#"{
+    "image": { "width": 4, "height": 4, "samples_per_pixel": -2 },
+    "materials": [
+        { "material": "plastic" },
+        { "material": "metalic", "color": { "r": 1, "g": 1, "b": 1 }, "fuzziness": NaN },
+        { "material": "emissive", "color": { "r": 1, "g": 1, "b": 1 }, "intensity": -1 }
+    ],
+    "camera": {
+        "position": { "x": 0, "y": 0, "z": 1 },
+        "look_at": { "x": 0, "y": 2, "z": 1 },
+        "focal_length": 1
+    },
+    "world": [
+        { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 3 },
+        { "hittable": "teapot", "material": 0 }
+    ]
+}"#;
+
+    let diagnostics = validate_scene(text);
+    let found: Vec<(&str, usize, usize)> = diagnostics
+        .iter()
+        .map(|d| (d.path.as_str(), d.line, d.column))
+        .collect();
+
+    assert_eq!(
+        found,
+        vec![
+            ("image.samples_per_pixel", 2, 62),
+            ("materials[0].material", 4, 23),
+            ("materials[1].fuzziness", 5, 84),
+            ("materials[2].intensity", 6, 85),
+            ("camera.look_at", 10, 20),
+            ("world[0].material", 14, 98),
+            ("world[1].hittable", 15, 23),
+        ]
+    );
+}
+
+#[test]
+fn validate_shapes() {
+    let text = r#"{
+    "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+    "materials": [{ "material": "dielectric", "refraction_index": 1.5 }],
+    "camera": {
+        "position": { "x": 0, "y": 0, "z": 1 },
+        "look_at": { "x": 0, "y": 0, "z": 0 },
+        "focal_length": 1,
+        "vertical_fov": 180,
+        "shutter": { "open": 1, "close": 0.5 },
+        "lens": { "aperture": -1, "blades": 2 }
+    },
+    "world": [
+        { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 0,
+          "translate": { "x": 1, "y": 0, "z": 0 }, "rotate": { "x": 0, "y": 45 },
+          "scale": { "x": 1, "y": 0, "z": 1 } },
+        { "hittable": "cylinder", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "height": 0,
+          "capped": 1, "material": 0 },
+        { "hittable": "plane", "position": { "x": 0, "y": 0, "z": 0 }, "normal": { "x": 0, "y": 0, "z": 0 },
+          "material": 0 },
+        { "hittable": "heightfield", "file": "terrain.png", "position": { "x": 0, "y": 0, "z": 0 },
+          "size": { "x": 10, "y": 0, "z": 10 }, "columns": 0, "material": 0 },
+        { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 0,
+          "end_position": { "x": 1, "y": 0, "z": true },
+          "motion": { "scale": { "x": 0, "y": 1, "z": 1 }, "motion": {} } }
+    ]
+}"#;
+
+    let diagnostics = validate_scene(text);
+    let found: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(
+        found,
+        vec![
+            "camera.vertical_fov",
+            "camera.vertical_fov",
+            "camera.shutter.close",
+            "camera.lens.aperture",
+            "camera.lens.blades",
+            "world[0].rotate",
+            "world[0].scale",
+            "world[1].height",
+            "world[1].capped",
+            "world[2].normal",
+            "world[3].size.y",
+            "world[3].columns",
+            "world[4].end_position.z",
+            "world[4].motion.scale",
+            "world[4].motion.motion"
+        ]
+    );
+}
+
+#[test]
+fn validate_csg() {
+    let text = r#"{
+    "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+    "materials": [{ "material": "dielectric", "refraction_index": 1.5 }],
+    "camera": {
+        "position": { "x": 0, "y": 0, "z": 1 },
+        "look_at": { "x": 0, "y": 0, "z": 0 },
+        "focal_length": 1
+    },
+    "world": [
+        { "hittable": "csg", "operation": "difference",
+          "left": { "hittable": "cylinder", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "height": 1,
+                    "material": 0 },
+          "right": { "hittable": "csg", "operation": "union",
+                     "left": { "hittable": "sphere", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "material": 3 },
+                     "right": { "hittable": "cone", "position": { "x": 0, "y": 0, "z": 0 }, "radius": 1, "height": 1,
+                                "capped": false, "material": 0 } } },
+        { "hittable": "csg", "operation": "xor",
+          "left": { "hittable": "plane", "position": { "x": 0, "y": 0, "z": 0 }, "normal": { "x": 0, "y": 1, "z": 0 },
+                    "material": 0 } }
+    ]
+}"#;
+
+    let diagnostics = validate_scene(text);
+    let found: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(
+        found,
+        vec![
+            "world[0].right.left.material",
+            "world[0].right.right",
+            "world[1]",
+            "world[1].operation",
+            "world[1].left",
+        ]
+    );
+}
+
+#[test]
+fn validate_projections() {
+    let cameras = [
+        (
+            r#""projection": "orthographic", "view_height": 0, "lens": { "aperture": 1 }"#,
+            vec!["camera.view_height", "camera.lens"],
+        ),
+        (
+            r#""projection": "fisheye", "vertical_fov": 360, "mapping": "stereographic""#,
+            vec!["camera.mapping"],
+        ),
+        (
+            r#""projection": "fisheye", "focal_length": 1"#,
+            vec!["camera", "camera.focal_length"],
+        ),
+        (
+            r#""projection": "equirectangular", "horizontal_fov": 90, "mapping": "equisolid""#,
+            vec!["camera.horizontal_fov", "camera.mapping"],
+        ),
+        (r#""projection": "cylindrical""#, vec!["camera.projection"]),
+        (r#""vertical_fov": 180"#, vec!["camera.vertical_fov"]),
+    ];
+
+    for (fields, expected) in cameras {
+        let text = format!(
+            r#"{{
+    "image": {{ "width": 4, "height": 4, "samples_per_pixel": 1 }},
+    "materials": [],
+    "camera": {{ "position": {{ "x": 0, "y": 0, "z": 1 }}, "look_at": {{ "x": 0, "y": 0, "z": 0 }}, {} }},
+    "world": []
+}}"#,
+            fields
+        );
+        let diagnostics = validate_scene(&text);
+        let found: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(found, expected, "{}", fields);
+    }
+}
+
+#[test]
+fn validate_lights() {
+    let text = r#"{
+    "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+    "materials": [],
+    "camera": { "position": { "x": 0, "y": 0, "z": 1 }, "look_at": { "x": 0, "y": 0, "z": 0 }, "focal_length": 1 },
+    "world": [],
+    "lights": [
+        { "light": "point", "position": { "x": 0, "y": 5, "z": 0 }, "color": { "r": 1, "g": 1, "b": 1 },
+          "intensity": -1 },
+        { "light": "spot", "position": { "x": 0, "y": 5, "z": 0 }, "direction": { "x": 0, "y": 0, "z": 0 },
+          "color": { "r": 1, "g": 1, "b": 1 }, "intensity": 1, "cone_angle": 200, "softness": 2 },
+        { "light": "directional", "direction": { "x": 0, "y": -1, "z": 0 }, "intensity": 1 },
+        { "light": "area" }
+    ]
+}"#;
+
+    let diagnostics = validate_scene(text);
+    let found: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(
+        found,
+        vec![
+            "lights[0].intensity",
+            "lights[1].direction",
+            "lights[1].cone_angle",
+            "lights[1].softness",
+            "lights[2]",
+            "lights[3].light",
+        ]
+    );
+}
+
+#[test]
+fn validate_syntax_error() {
+    let diagnostics = validate_scene("{\n  \"image\": [1, 2\n}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (3, 1));
+
+    // What serde_json rejects at load is a syntax error here too.
+    for number in ["01", "1.", ".5", "+1", "1e", "1.5e+", "-", "0x10", "1_0"] {
+        assert!(!is_json_number(number), "{}", number);
+        let text = format!("{{ \"image\": {} }}", number);
+        assert!(serde_json::from_str::<serde_json::Value>(&text).is_err());
+        assert_eq!(validate_scene(&text).len(), 1);
+    }
+    for number in ["0", "-0", "10", "1.25", "-0.5e10", "2E-3", "1e+2"] {
+        assert!(is_json_number(number), "{}", number);
+    }
+    let diagnostics = validate_scene("{\u{a0}\"image\": 1 }");
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (1, 2));
+}
+
+#[test]
+fn validate_glass() {
+    let text = r#"{
+    "image": { "width": 4, "height": 4, "samples_per_pixel": 1 },
+    "materials": [
+        { "material": "dielectric", "refraction_index": 1.5, "roughness": 0.2,
+          "absorption": { "color": { "r": 0.8, "g": 0.9, "b": 1 }, "distance": 2 } },
+        { "material": "dielectric", "refraction_index": 1.5, "roughness": 2,
+          "absorption": { "color": { "r": 0.8, "g": 1.5, "b": 1 }, "distance": 0 } },
+        { "material": "dielectric", "refraction_index": 1.5, "absorption": 1 },
+        { "material": "dielectric", "refraction_index": 0 }
+    ],
+    "camera": { "position": { "x": 0, "y": 0, "z": 1 }, "look_at": { "x": 0, "y": 0, "z": 0 }, "focal_length": 1 },
+    "world": []
+}"#;
+
+    let diagnostics = validate_scene(text);
+    let found: Vec<&str> = diagnostics.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(
+        found,
+        vec![
+            "materials[1].roughness",
+            "materials[1].absorption.color.g",
+            "materials[1].absorption.distance",
+            "materials[2].absorption",
+            "materials[3].refraction_index",
+        ]
+    );
+}
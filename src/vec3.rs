@@ -106,7 +106,17 @@ impl Vec3 {
         *self - (normal * 2.0 * self.dot(normal))
     }
 
-    #[allow(dead_code)]
+    // Two unit vectors perpendicular to self and to each other, self must be
+    // a unit vector.
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let other = match f64::abs(self.x) > 0.9 {
+            true => Vec3::new(0.0, 1.0, 0.0),
+            false => Vec3::new(1.0, 0.0, 0.0),
+        };
+        let tangent = self.cross(other).unit();
+        (tangent, self.cross(tangent))
+    }
+
     pub fn refract(&self, normal: Vec3, ratio: f64) -> Vec3 {
         // Snell's Law
         let cost_theta = f64::min(-self.dot(normal), 1.0);
@@ -209,6 +219,20 @@ impl ops::Div<f64> for Vec3 {
     }
 }
 
+// Component by axis, 0 is x, 1 is y and 2 is z.
+impl ops::Index<usize> for Vec3 {
+    type Output = f64;
+    #[inline]
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 axis {} out of range", axis),
+        }
+    }
+}
+
 pub type Point3 = Vec3;
 pub type Color = Vec3;
 
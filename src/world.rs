@@ -1,37 +1,362 @@
+use rand::prelude::*;
+use std::any::Any;
+use std::sync::OnceLock;
+
+use super::aabb::*;
+use super::bvh::*;
 use super::hittable::*;
+use super::light::*;
+use super::mesh::*;
+use super::quad::*;
 use super::ray::*;
 use super::sphere::*;
+use super::triangle::*;
+use super::vec3::*;
+
+// Identifies an object in a World. Handles stay valid when other objects are
+// removed and are never reused for a different object.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectHandle(usize);
 
+// BVH over the bounded objects, objects without a bounding box are tested
+// against every ray.
+struct Acceleration {
+    bvh: Bvh,
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+// Objects are kept in a BVH that is built when the first ray is traced after
+// objects were added or removed. Emissive spheres and quads are gathered as
+// lights the same way, other emissive objects are only found by chance.
+// Lights that aren't objects, like point lights, are added on their own.
 pub struct World {
-    spheres: Vec<Sphere>,
+    objects: Vec<Option<Box<dyn Hittable + Send + Sync>>>,
+    count: usize,
+    acceleration: OnceLock<Acceleration>,
+    emitters: OnceLock<Vec<(ObjectHandle, Box<dyn Light>)>>,
+    lights: Vec<Box<dyn Light>>,
 }
 
 impl World {
     pub fn new() -> World {
         World {
-            spheres: Vec::<Sphere>::new(),
+            objects: Vec::new(),
+            count: 0,
+            acceleration: OnceLock::new(),
+            emitters: OnceLock::new(),
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn add<H: Hittable + Send + Sync>(&mut self, object: H) -> ObjectHandle {
+        self.add_boxed(Box::new(object))
+    }
+
+    pub fn add_boxed(&mut self, object: Box<dyn Hittable + Send + Sync>) -> ObjectHandle {
+        self.objects.push(Some(object));
+        self.count += 1;
+        self.acceleration = OnceLock::new();
+        self.emitters = OnceLock::new();
+        ObjectHandle(self.objects.len() - 1)
+    }
+
+    pub fn add_sphere(&mut self, s: Sphere) -> ObjectHandle {
+        self.add(s)
+    }
+
+    pub fn add_triangle(&mut self, t: Triangle) -> ObjectHandle {
+        self.add(t)
+    }
+
+    pub fn add_mesh(&mut self, m: Mesh) -> ObjectHandle {
+        self.add(m)
+    }
+
+    pub fn remove(&mut self, handle: ObjectHandle) -> Option<Box<dyn Hittable + Send + Sync>> {
+        let object = self.objects.get_mut(handle.0)?.take();
+        if object.is_some() {
+            self.count -= 1;
+            self.acceleration = OnceLock::new();
+            self.emitters = OnceLock::new();
+        }
+        object
+    }
+
+    pub fn get(&self, handle: ObjectHandle) -> Option<&(dyn Hittable + Send + Sync)> {
+        self.objects.get(handle.0)?.as_deref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectHandle, &(dyn Hittable + Send + Sync))> {
+        self.objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| Some((ObjectHandle(index), object.as_deref()?)))
+    }
+
+    // All objects of one concrete type, e.g. every Sphere in the world.
+    pub fn objects_of<T: Hittable>(&self) -> impl Iterator<Item = &T> {
+        self.iter()
+            .filter_map(|(_, object)| (object as &dyn Any).downcast_ref::<T>())
+    }
+
+    fn acceleration(&self) -> &Acceleration {
+        self.acceleration.get_or_init(|| {
+            let mut boxes = Vec::<Aabb>::new();
+            let mut bounded = Vec::new();
+            let mut unbounded = Vec::new();
+
+            for (index, object) in self.objects.iter().enumerate() {
+                match object.as_ref().map(|o| o.bounding_box()) {
+                    None => (),
+                    Some(Some(aabb)) => {
+                        boxes.push(aabb);
+                        bounded.push(index);
+                    }
+                    Some(None) => unbounded.push(index),
+                }
+            }
+
+            Acceleration {
+                bvh: Bvh::build(&boxes),
+                bounded,
+                unbounded,
+            }
+        })
+    }
+
+    pub fn add_light<L: Light>(&mut self, light: L) {
+        self.lights.push(Box::new(light));
+    }
+
+    // The emissive objects followed by the added lights.
+    pub fn lights(&self) -> impl Iterator<Item = &dyn Light> {
+        self.emitters()
+            .iter()
+            .map(|(_, light)| light)
+            .chain(self.lights.iter())
+            .map(|light| light.as_ref())
+    }
+
+    // All added lights of one concrete type, e.g. every PointLight.
+    pub fn lights_of<T: Light>(&self) -> impl Iterator<Item = &T> {
+        self.lights
+            .iter()
+            .filter_map(|light| (light.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    fn emitters(&self) -> &[(ObjectHandle, Box<dyn Light>)] {
+        self.emitters.get_or_init(|| {
+            let mut emitters: Vec<(ObjectHandle, Box<dyn Light>)> = Vec::new();
+            for (handle, object) in self.iter() {
+                if let Some(sphere) = (object as &dyn Any).downcast_ref::<Sphere>() {
+                    if is_emissive(sphere.material()) && sphere.radius() > 0.0 {
+                        emitters.push((handle, Box::new(sphere.clone())));
+                    }
+                }
+            }
+            for (handle, object) in self.iter() {
+                if let Some(quad) = (object as &dyn Any).downcast_ref::<Quad>() {
+                    if is_emissive(quad.material()) {
+                        emitters.push((handle, Box::new(quad.clone())));
+                    }
+                }
+            }
+            emitters
+        })
+    }
+
+    // Direction towards one of the lights picked at random, the density
+    // includes picking the light.
+    pub fn sample_light(&self, point: Point3) -> Option<LightSample> {
+        let count = self.emitters().len() + self.lights.len();
+        if count == 0 {
+            return None;
+        }
+        let light = self.lights().nth(thread_rng().gen_range(0..count))?;
+        let mut sample = light.sample(point)?;
+        sample.pdf /= count as f64;
+        Some(sample)
+    }
+
+    // Density of sample_light picking direction from point through the light
+    // a scattered ray hit, 0 when the object hit isn't one of the lights.
+    // Lights further along the direction are hidden behind it, so sampling
+    // them never lights the point.
+    pub fn light_pdf(&self, point: Point3, direction: Vec3, object: ObjectHandle) -> f64 {
+        let count = self.emitters().len() + self.lights.len();
+        match self.emitters().iter().find(|(handle, _)| *handle == object) {
+            Some((_, light)) => light.pdf(point, direction) / count as f64,
+            None => 0.0,
         }
     }
 
-    pub fn add_sphere(&mut self, s: Sphere) {
-        self.spheres.push(s);
+    // Builds the BVH now if needed, rather than on the first ray.
+    pub fn bvh_stats(&self) -> &BvhStats {
+        self.acceleration().bvh.stats()
     }
 
     pub fn test_camera_ray(&self, ray: &Ray) -> Option<Hit> {
+        self.hit_object(ray).map(|(_, hit)| hit)
+    }
+
+    // The closest hit along the ray and the object it is on.
+    pub fn hit_object(&self, ray: &Ray) -> Option<(ObjectHandle, Hit)> {
         const MINIMUM_SOLUTION: f64 = 1e-6;
+        let acceleration = self.acceleration();
+        let object = |index: usize| self.objects[index].as_ref().unwrap();
+
         let mut maximum_solution = f64::INFINITY;
-        let mut out: Option<Hit> = None;
-
-        for object in self.spheres.iter() {
-            match object.hit(ray, MINIMUM_SOLUTION, maximum_solution) {
-                None => (),
-                Some(hit) => {
-                    maximum_solution = hit.solution;
-                    out = Some(hit);
-                }
+        let mut out: Option<(ObjectHandle, Hit)> = None;
+
+        for index in acceleration.unbounded.iter() {
+            if let Some(hit) = object(*index).hit(ray, MINIMUM_SOLUTION, maximum_solution) {
+                maximum_solution = hit.solution;
+                out = Some((ObjectHandle(*index), hit));
+            }
+        }
+
+        // Every hit the BVH is handed is closer than the ones before it, so
+        // the last object hit is the one the closest hit is on.
+        let mut closest_index = 0;
+        let closest = acceleration.bvh.hit(
+            ray,
+            MINIMUM_SOLUTION,
+            maximum_solution,
+            |index, minimum, maximum| {
+                let hit = object(acceleration.bounded[index]).hit(ray, minimum, maximum)?;
+                closest_index = acceleration.bounded[index];
+                Some(hit)
+            },
+        );
+
+        closest
+            .map(|hit| (ObjectHandle(closest_index), hit))
+            .or(out)
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::*;
+    use crate::material::*;
+    use crate::transform::*;
+
+    // A primitive defined outside of the crate's own ones.
+    struct Floor;
+
+    impl Hittable for Floor {
+        fn hit(&self, ray: &Ray, minimum: f64, maximum: f64) -> Option<Hit> {
+            let solution = -ray.origin.y / ray.direction.y;
+            if solution < minimum || solution > maximum {
+                return None;
             }
+            Some(Hit::new(
+                ray.at(solution),
+                Vec3::new(0.0, 1.0, 0.0),
+                solution,
+                true,
+                Material::Diffuse(Color::new(0.5, 0.5, 0.5)),
+            ))
         }
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut world = World::new();
+        let floor = world.add(Floor);
+        let sphere = world.add_sphere(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            0.5,
+            Material::Dielectric(1.5),
+        ));
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.objects_of::<Sphere>().count(), 1);
+        assert_eq!(world.bvh_stats().primitive_count, 1);
+
+        let ray = Ray::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(world.test_camera_ray(&ray).unwrap().solution, 1.5);
+
+        assert!(world.remove(sphere).is_some());
+        assert!(world.remove(sphere).is_none());
+        assert!(world.get(sphere).is_none());
+        assert!(world.get(floor).is_some());
+        assert_eq!(world.len(), 1);
+        assert_eq!(world.test_camera_ray(&ray).unwrap().solution, 3.0);
+
+        // Handles are not reused.
+        let other = world.add_sphere(Sphere::new(
+            Point3::new(0.0, 1.0, 0.0),
+            0.5,
+            Material::Dielectric(1.5),
+        ));
+        assert_ne!(other, sphere);
+        assert_eq!(
+            world.iter().map(|(h, _)| h).collect::<Vec<_>>(),
+            vec![floor, other]
+        );
+    }
+
+    #[test]
+    fn gather_lights() {
+        let mut world = World::new();
+        let lamp = Material::Emissive(Color::new(1.0, 1.0, 1.0), 2.0);
+        world.add(Floor);
+        let bulb = world.add_sphere(Sphere::new(Point3::new(0.0, 4.0, 0.0), 1.0, lamp));
+        let panel = world.add(Quad::xz((-1.0, 1.0), (-1.0, 1.0), 5.0, lamp));
+        assert_eq!(world.lights().count(), 2);
+
+        world.remove(bulb);
+        assert_eq!(world.lights().count(), 1);
+        let point = Point3::new(0.0, 6.0, 0.0);
+        let sample = world.sample_light(point).unwrap();
+        let pdf = world.light_pdf(point, sample.direction, panel);
+        assert!((sample.pdf - pdf).abs() < 1e-9);
+    }
+
+    #[test]
+    fn overlapping_lights() {
+        let mut world = World::new();
+        let lamp = Material::Emissive(Color::new(1.0, 1.0, 1.0), 2.0);
+        let floor = world.add(Floor);
+        let bulb = world.add_sphere(Sphere::new(Point3::new(0.0, 4.0, 0.0), 1.0, lamp));
+        let panel = world.add(Quad::xz((-1.0, 1.0), (-1.0, 1.0), 5.0, lamp));
+        let hidden = world.add(Transformed::new(
+            Sphere::new(Point3::new(0.0, 0.0, 0.0), 0.5, lamp),
+            Transform::translation(Vec3::new(0.0, 7.0, 0.0)),
+        ));
+        assert_eq!(world.lights().count(), 2);
+
+        // Looking down, the panel covers the bulb and only the panel's own
+        // density counts for a ray that hits it.
+        let (point, down) = (Point3::new(0.0, 5.25, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let (object, _) = world.hit_object(&Ray::new(point, down)).unwrap();
+        assert_eq!(object, panel);
+        let sphere_pdf = 1.0 / (2.0 * PI * (1.0 - f64::sqrt(1.0 - 1.0 / (1.25 * 1.25))));
+        let quad_pdf = 0.25 * 0.25 / 4.0;
+        assert!((world.light_pdf(point, down, panel) - quad_pdf / 2.0).abs() < 1e-9);
+        assert!((world.light_pdf(point, down, bulb) - sphere_pdf / 2.0).abs() < 1e-9);
+        assert_eq!(world.light_pdf(point, down, floor), 0.0);
 
-        out
+        // Emissive objects that aren't lights are only found by scattering.
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        let (object, _) = world.hit_object(&Ray::new(point, up)).unwrap();
+        assert_eq!(object, hidden);
+        assert_eq!(world.light_pdf(point, up, hidden), 0.0);
     }
 }